@@ -0,0 +1,380 @@
+use {
+    gstreamer::{prelude::*, *},
+    std::{str::FromStr, sync::Mutex},
+};
+
+/// Name of the application message posted by an adaptive demuxer once its manifest is read
+pub const MANIFEST_RECEIVED: &str = "aug-manifest-received";
+
+/// Demuxers of HLS and DASH manifests
+const ADAPTIVE_DEMUXERS: [&str; 4] = ["hlsdemux", "hlsdemux2", "dashdemux", "dashdemux2"];
+
+/// A bitrate variant of an adaptive stream, as listed in its manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Bandwidth announced by the manifest, in bits per second
+    pub bandwidth: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl Variant {
+    pub fn label(&self) -> String {
+        let bitrate = format!("{:.1} Mbps", self.bandwidth as f64 / 1_000_000.0);
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => format!("{}x{} @ {}", width, height, bitrate),
+            _ => bitrate,
+        }
+    }
+}
+
+/// Quality selection of adaptive streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QualityLock {
+    /// Let the demuxer follow the available bandwidth
+    #[default]
+    Auto,
+    /// Stay on the variant with this height, e.g. 720
+    Resolution(u32),
+    /// Stay on the best variant under this bitrate, in bits per second
+    Bitrate(u64),
+}
+
+impl FromStr for QualityLock {
+    type Err = String;
+
+    /// Parse `auto`, a resolution such as `720p` or a bitrate in kbps such as `2500k`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        if value == "auto" {
+            Ok(Self::Auto)
+        } else if let Some(height) = value.strip_suffix('p') {
+            height
+                .parse()
+                .map(Self::Resolution)
+                .map_err(|_| format!("Invalid resolution: {}", value))
+        } else if let Some(kbps) = value.strip_suffix('k') {
+            kbps.parse::<u64>()
+                .map(|kbps| Self::Bitrate(kbps * 1000))
+                .map_err(|_| format!("Invalid bitrate: {}", value))
+        } else {
+            Err(format!(
+                "Invalid quality {}, expected auto, a resolution (720p) or a bitrate (2500k)",
+                value
+            ))
+        }
+    }
+}
+
+pub fn is_adaptive_demuxer(element: &Element) -> bool {
+    element
+        .factory()
+        .is_some_and(|factory| ADAPTIVE_DEMUXERS.contains(&factory.name().as_str()))
+}
+
+/// Collect the manifest flowing into an adaptive demuxer. Once complete, it is posted
+/// from the demuxer as an application message named [`MANIFEST_RECEIVED`].
+pub fn watch_manifest(demuxer: &Element) {
+    let Some(sink_pad) = demuxer.static_pad("sink") else {
+        return;
+    };
+    let manifest = Mutex::new(Vec::new());
+    sink_pad.add_probe(
+        PadProbeType::BUFFER | PadProbeType::EVENT_DOWNSTREAM,
+        move |pad, info| {
+            if let Some(buffer) = info.buffer() {
+                if let Ok(map) = buffer.map_readable() {
+                    manifest.lock().unwrap().extend_from_slice(map.as_slice());
+                }
+                return PadProbeReturn::Ok;
+            }
+            if info
+                .event()
+                .is_some_and(|event| event.type_() == EventType::Eos)
+            {
+                let manifest = String::from_utf8_lossy(&manifest.lock().unwrap()).into_owned();
+                if let Some(demuxer) = pad.parent_element() {
+                    let structure = Structure::builder(MANIFEST_RECEIVED)
+                        .field("manifest", manifest)
+                        .build();
+                    demuxer
+                        .post_message(
+                            message::Application::builder(structure)
+                                .src(&demuxer)
+                                .build(),
+                        )
+                        .ok();
+                }
+                return PadProbeReturn::Remove;
+            }
+            PadProbeReturn::Ok
+        },
+    );
+}
+
+/// List the video variants of an HLS master playlist or a DASH MPD, sorted by bandwidth
+pub fn parse_variants(manifest: &str) -> Vec<Variant> {
+    let mut variants = if manifest.trim_start().starts_with("#EXTM3U") {
+        parse_hls_variants(manifest)
+    } else {
+        parse_dash_variants(manifest)
+    };
+    variants.sort_by_key(|variant| variant.bandwidth);
+    variants.dedup();
+    variants
+}
+
+fn parse_hls_variants(manifest: &str) -> Vec<Variant> {
+    manifest
+        .lines()
+        .filter_map(|line| line.strip_prefix("#EXT-X-STREAM-INF:"))
+        .filter_map(|attributes| {
+            let mut variant = Variant {
+                bandwidth: 0,
+                width: None,
+                height: None,
+            };
+            // Quoted values such as CODECS may contain commas, but never the keys we read
+            for attribute in attributes.split(',') {
+                match attribute.split_once('=') {
+                    Some(("BANDWIDTH", value)) => variant.bandwidth = value.parse().ok()?,
+                    Some(("RESOLUTION", value)) => {
+                        let (width, height) = value.split_once('x')?;
+                        variant.width = width.parse().ok();
+                        variant.height = height.parse().ok();
+                    }
+                    _ => (),
+                }
+            }
+            Some(variant)
+        })
+        .collect()
+}
+
+/// Representations of an MPD, which inherit the attributes of their AdaptationSet
+fn parse_dash_variants(manifest: &str) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut adaptation_set = Vec::new();
+    for tag in xml_tags(manifest) {
+        match (tag.name, tag.closing) {
+            ("AdaptationSet", false) => adaptation_set = tag.attributes,
+            ("AdaptationSet", true) => adaptation_set.clear(),
+            ("Representation", false) => {
+                let attribute = |name: &str| {
+                    tag.attributes
+                        .iter()
+                        .chain(&adaptation_set)
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| *value)
+                };
+                let is_audio = attribute("contentType") == Some("audio")
+                    || attribute("mimeType").is_some_and(|mime| mime.starts_with("audio/"));
+                // Audio representations carry no size
+                let height = attribute("height").and_then(|height| height.parse().ok());
+                let bandwidth = attribute("bandwidth").and_then(|value| value.parse().ok());
+                if let (Some(bandwidth), Some(height)) = (bandwidth, height)
+                    && !is_audio
+                {
+                    variants.push(Variant {
+                        bandwidth,
+                        width: attribute("width").and_then(|width| width.parse().ok()),
+                        height: Some(height),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+    variants
+}
+
+/// Start or end tag of an XML document
+struct XmlTag<'a> {
+    /// Name without its namespace prefix
+    name: &'a str,
+    /// Is it an end tag, `</name>`?
+    closing: bool,
+    attributes: Vec<(&'a str, &'a str)>,
+}
+
+/// Tags of an XML document, in order. Comments, declarations and processing instructions
+/// are skipped, attribute values are not unescaped.
+fn xml_tags(document: &str) -> Vec<XmlTag<'_>> {
+    let mut tags = Vec::new();
+    let mut rest = document;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with('!') || rest.starts_with('?') {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let closing = rest.starts_with('/');
+        let body = rest.trim_start_matches('/');
+        let name_end = body
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .unwrap_or(body.len());
+        let name = &body[..name_end];
+        let name = name.rsplit(':').next().unwrap_or(name);
+        let mut attributes = Vec::new();
+        let mut body = &body[name_end..];
+        loop {
+            body = body.trim_start();
+            let Some(equals) = body.find('=') else {
+                break;
+            };
+            let key = body[..equals].trim_end();
+            if key.is_empty() || key.contains(['>', '/']) {
+                break;
+            }
+            let value = body[equals + 1..].trim_start();
+            let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                break;
+            };
+            let Some(end) = value[1..].find(quote) else {
+                break;
+            };
+            attributes.push((key, &value[1..end + 1]));
+            body = &value[end + 2..];
+        }
+        rest = body.find('>').map_or("", |end| &body[end + 1..]);
+        tags.push(XmlTag {
+            name,
+            closing,
+            attributes,
+        });
+    }
+    tags
+}
+
+/// Restrict the bitrates the demuxer may pick to follow `lock`
+pub fn apply_quality_lock(demuxer: &Element, variants: &[Variant], lock: QualityLock) {
+    let (min_bitrate, max_bitrate) = match lock {
+        QualityLock::Auto => (0, 0),
+        QualityLock::Bitrate(bitrate) => (0, bitrate),
+        QualityLock::Resolution(height) => {
+            match variants
+                .iter()
+                .rev()
+                .find(|variant| variant.height == Some(height))
+            {
+                Some(variant) => (variant.bandwidth, variant.bandwidth),
+                None => (0, 0),
+            }
+        }
+    };
+
+    // hlsdemux2 and dashdemux2 take a bitrate range, the older demuxers pick the variants
+    // matching the connection speed they are told, in kbps
+    if demuxer.has_property("min-bitrate") && demuxer.has_property("max-bitrate") {
+        demuxer.set_property(
+            "min-bitrate",
+            u32::try_from(min_bitrate).unwrap_or(u32::MAX),
+        );
+        demuxer.set_property(
+            "max-bitrate",
+            u32::try_from(max_bitrate).unwrap_or(u32::MAX),
+        );
+    } else if demuxer.has_property("connection-speed") {
+        let kbps = u32::try_from(max_bitrate / 1000).unwrap_or(u32::MAX);
+        demuxer.set_property("connection-speed", kbps);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dash_attributes_on_several_lines() {
+        let manifest = r#"<?xml version="1.0"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011">
+  <Period>
+    <AdaptationSet mimeType="video/mp4">
+      <Representation id="1"
+                      bandwidth="2500000"
+                      width="1280"
+                      height="720"/>
+      <Representation
+          id="2" bandwidth="800000" width="640" height="360" />
+    </AdaptationSet>
+    <AdaptationSet mimeType="audio/mp4">
+      <Representation id="3" bandwidth="128000"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+        assert_eq!(
+            parse_variants(manifest),
+            vec![
+                Variant {
+                    bandwidth: 800_000,
+                    width: Some(640),
+                    height: Some(360),
+                },
+                Variant {
+                    bandwidth: 2_500_000,
+                    width: Some(1280),
+                    height: Some(720),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dash_size_inherited_from_adaptation_set() {
+        let manifest = r#"<MPD>
+  <!-- <Representation bandwidth="1" height="1"/> -->
+  <AdaptationSet contentType="video" width="1920" height="1080">
+    <Representation bandwidth="5000000"/>
+    <Representation bandwidth="3000000" width="1280" height="720"/>
+  </AdaptationSet>
+  <AdaptationSet contentType="audio">
+    <Representation bandwidth="96000"/>
+  </AdaptationSet>
+</MPD>"#;
+        assert_eq!(
+            parse_variants(manifest),
+            vec![
+                Variant {
+                    bandwidth: 3_000_000,
+                    width: Some(1280),
+                    height: Some(720),
+                },
+                Variant {
+                    bandwidth: 5_000_000,
+                    width: Some(1920),
+                    height: Some(1080),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hls_variants() {
+        let manifest = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"
+low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.5\"
+audio.m3u8
+";
+        assert_eq!(
+            parse_variants(manifest),
+            vec![
+                Variant {
+                    bandwidth: 64_000,
+                    width: None,
+                    height: None,
+                },
+                Variant {
+                    bandwidth: 1_280_000,
+                    width: Some(640),
+                    height: Some(360),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use {
+    crate::{config::NormalizationMode, loudness},
+    gstreamer::{prelude::*, *},
+};
+
+/// The `audio-filter` bin installed in playbin.
+/// `audioconvert ! [rgvolume !] volume ! rglimiter ! audioconvert`
+pub struct AudioFilter {
+    /// The bin with ghost pads, ready to be set as playbin's `audio-filter`
+    pub bin: Bin,
+    /// ReplayGain element, present when the normalization is enabled
+    pub rgvolume: Option<Element>,
+    /// Volume element used to amplify the audio above 100%
+    pub boost: Element,
+}
+
+impl AudioFilter {
+    pub fn build(normalization: NormalizationMode, target_lufs: f64) -> Result<Self, glib::Error> {
+        let rgvolume = match normalization {
+            NormalizationMode::Off => "",
+            NormalizationMode::Track | NormalizationMode::Album => "rgvolume name=rgvolume ! ",
+        };
+        // The limiter sits after the boost so an amplified signal never clips
+        let bin = parse::bin_from_description(
+            &format!(
+                "audioconvert ! {}volume name=boost ! rglimiter ! audioconvert",
+                rgvolume
+            ),
+            true,
+        )?;
+        bin.set_property("name", "audio-filter");
+
+        let rgvolume = bin.by_name("rgvolume");
+        if let Some(rgvolume) = &rgvolume {
+            rgvolume.set_property("album-mode", normalization == NormalizationMode::Album);
+            // rgvolume does not add the pre-amp to the fallback gain of untagged media
+            let pre_amp = loudness::pre_amp_for_target(target_lufs);
+            rgvolume.set_property("pre-amp", pre_amp);
+            rgvolume.set_property("fallback-gain", pre_amp);
+        }
+        // The element is created by the description above, so it is always present
+        let boost = bin.by_name("boost").unwrap();
+
+        Ok(Self {
+            bin,
+            rgvolume,
+            boost,
+        })
+    }
+}
@@ -0,0 +1,472 @@
+use {
+    crate::{
+        config::{OriginType, parse_timestamp},
+        history::default_title,
+        media_player::MediaPlayerErrors,
+        media_settings::{config_dir, save_key_file, uri_group},
+    },
+    clap::ValueEnum,
+    gstreamer::{
+        ClockTime,
+        glib::{KeyFile, KeyFileFlags},
+    },
+    serde::{Deserialize, Serialize},
+    std::path::{Path, PathBuf},
+};
+
+/// Length of the last WebVTT chapter when the duration of the media is unknown
+const LAST_CHAPTER_LENGTH: ClockTime = ClockTime::from_seconds(1);
+
+/// Bookmark file formats
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookmarkFormat {
+    /// JSON object listing the bookmarks with their notes
+    Json,
+    /// WebVTT chapters, with the notes in `NOTE` blocks
+    Vtt,
+}
+
+impl BookmarkFormat {
+    /// Format matching the extension of `path`, e.g. `talk.vtt`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::from_str(extension, true).ok()
+    }
+}
+
+/// A moment of a media, marked by the user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub position: ClockTime,
+    pub name: String,
+    /// Free text, possibly on several lines
+    pub note: String,
+}
+
+/// A media put aside to watch later
+#[derive(Debug, Clone)]
+pub struct WatchLater {
+    pub uri: String,
+    /// Title tag of the media, or its file name
+    pub title: String,
+    /// Where the playback stopped, `None` to start over
+    pub position: Option<ClockTime>,
+    /// Origin the media was opened with, `None` if its options were not saved
+    pub origin: Option<OriginType>,
+}
+
+/// Bookmark file in JSON:
+/// `{"uri": ..., "bookmarks": [{"position": <seconds>, "name": ..., "note": ...}]}`
+#[derive(Serialize, Deserialize)]
+struct JsonBookmarks {
+    #[serde(default)]
+    uri: String,
+    bookmarks: Vec<JsonBookmark>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonBookmark {
+    /// In seconds
+    position: f64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    note: String,
+}
+
+/// Bookmarks and watch-later marks of the media, stored as a key file with one group per
+/// URI. The bookmarks of a group are numbered from 1: `position1`, `name1`, `note1`...
+/// The media to watch later also keep their resume `position` and origin options.
+pub struct Bookmarks {
+    path: PathBuf,
+    key_file: KeyFile,
+}
+
+impl Bookmarks {
+    /// Load the bookmarks from `bookmarks.ini` in [`config_dir`]. A missing or unreadable
+    /// file gives no bookmark.
+    pub fn load() -> Self {
+        Self::load_from(config_dir().join("bookmarks.ini"))
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let key_file = KeyFile::new();
+        key_file
+            .load_from_file(&path, KeyFileFlags::KEEP_COMMENTS)
+            .ok();
+        Self { path, key_file }
+    }
+
+    /// Bookmarks of the media, sorted by position
+    pub fn bookmarks(&self, uri: &str) -> Vec<Bookmark> {
+        let group = uri_group(uri);
+        let mut bookmarks = Vec::new();
+        for number in 1.. {
+            let Ok(position) = self.key_file.uint64(&group, &format!("position{}", number)) else {
+                break;
+            };
+            let string = |key: &str| {
+                self.key_file
+                    .string(&group, &format!("{}{}", key, number))
+                    .map_or_else(|_| String::new(), |value| value.to_string())
+            };
+            bookmarks.push(Bookmark {
+                position: ClockTime::from_mseconds(position),
+                name: string("name"),
+                note: string("note"),
+            });
+        }
+        bookmarks.sort_by_key(|bookmark| bookmark.position);
+        bookmarks
+    }
+
+    /// Replace the bookmarks of the media. The media is forgotten once it has no bookmark
+    /// and is not to watch later.
+    pub fn set_bookmarks(&self, uri: &str, bookmarks: &[Bookmark]) {
+        let group = uri_group(uri);
+        if bookmarks.is_empty() && !self.is_watch_later(uri) {
+            self.key_file.remove_group(&group).ok();
+            return;
+        }
+
+        // The numbered keys of the previous bookmarks
+        if let Ok(keys) = self.key_file.keys(&group) {
+            for key in keys.iter().filter(|key| {
+                let name = key.trim_end_matches(|character: char| character.is_ascii_digit());
+                name.len() < key.len() && ["position", "name", "note"].contains(&name)
+            }) {
+                self.key_file.remove_key(&group, key).ok();
+            }
+        }
+        self.key_file.set_string(&group, "uri", uri);
+        for (index, bookmark) in bookmarks.iter().enumerate() {
+            let number = index + 1;
+            self.key_file.set_uint64(
+                &group,
+                &format!("position{}", number),
+                bookmark.position.mseconds(),
+            );
+            self.key_file
+                .set_string(&group, &format!("name{}", number), &bookmark.name);
+            // Key files escape the line breaks of the notes
+            self.key_file
+                .set_string(&group, &format!("note{}", number), &bookmark.note);
+        }
+    }
+
+    pub fn is_watch_later(&self, uri: &str) -> bool {
+        self.key_file
+            .boolean(&uri_group(uri), "watch-later")
+            .unwrap_or(false)
+    }
+
+    /// Put the media aside to watch later with the `origin` it was opened with, or take it
+    /// back. Without `title`, the media is named after its file.
+    pub fn set_watch_later(
+        &self,
+        uri: &str,
+        title: Option<&str>,
+        origin: &OriginType,
+        watch_later: bool,
+    ) {
+        let group = uri_group(uri);
+        if watch_later {
+            self.key_file.set_string(&group, "uri", uri);
+            self.key_file.set_string(
+                &group,
+                "title",
+                &title.map_or_else(|| default_title(uri), str::to_string),
+            );
+            self.key_file.set_boolean(&group, "watch-later", true);
+            origin.store(&self.key_file, &group);
+        } else if self.key_file.has_group(&group) {
+            self.key_file.remove_key(&group, "watch-later").ok();
+            self.key_file.remove_key(&group, "position").ok();
+            OriginType::forget(&self.key_file, &group);
+            // Rewritten to forget the media if it has no bookmark either
+            self.set_bookmarks(uri, &self.bookmarks(uri));
+        }
+    }
+
+    /// Remember where the playback of the media stopped, if it is to watch later
+    pub fn set_position(&self, uri: &str, position: Option<ClockTime>) {
+        if !self.is_watch_later(uri) {
+            return;
+        }
+        let group = uri_group(uri);
+        match position {
+            Some(position) => {
+                self.key_file
+                    .set_uint64(&group, "position", position.mseconds());
+            }
+            None => {
+                self.key_file.remove_key(&group, "position").ok();
+            }
+        }
+    }
+
+    /// Media put aside to watch later
+    pub fn watch_later(&self) -> Vec<WatchLater> {
+        self.key_file
+            .groups()
+            .iter()
+            .filter_map(|group| self.watch_later_group(group.as_str()))
+            .collect()
+    }
+
+    /// The media at `uri`, if it is to watch later
+    pub fn watch_later_entry(&self, uri: &str) -> Option<WatchLater> {
+        self.watch_later_group(&uri_group(uri))
+    }
+
+    fn watch_later_group(&self, group: &str) -> Option<WatchLater> {
+        if !self.key_file.boolean(group, "watch-later").unwrap_or(false) {
+            return None;
+        }
+        let uri = self.key_file.string(group, "uri").ok()?;
+        let title = self
+            .key_file
+            .string(group, "title")
+            .map_or_else(|_| default_title(&uri), |title| title.to_string());
+        Some(WatchLater {
+            uri: uri.to_string(),
+            title,
+            position: self
+                .key_file
+                .uint64(group, "position")
+                .ok()
+                .map(ClockTime::from_mseconds),
+            origin: OriginType::load(&self.key_file, group),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), MediaPlayerErrors> {
+        save_key_file(&self.key_file, &self.path)
+    }
+}
+
+/// Write the bookmarks of `uri` to `path`, in the format given by its extension, JSON by
+/// default. `duration` ends the last WebVTT chapter.
+pub fn export(
+    uri: &str,
+    bookmarks: &[Bookmark],
+    duration: Option<ClockTime>,
+    path: &Path,
+) -> Result<(), MediaPlayerErrors> {
+    let contents = match BookmarkFormat::from_path(path).unwrap_or(BookmarkFormat::Json) {
+        BookmarkFormat::Json => to_json(uri, bookmarks),
+        BookmarkFormat::Vtt => to_vtt(bookmarks, duration),
+    };
+    std::fs::write(path, contents)
+        .map_err(|err| MediaPlayerErrors::ErrorSavingBookmarks(path.to_path_buf(), err))
+}
+
+/// Read the bookmarks of a JSON or WebVTT file, whatever its extension
+pub fn import(path: &Path) -> Result<Vec<Bookmark>, MediaPlayerErrors> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| MediaPlayerErrors::ErrorReadingBookmarks(path.to_path_buf(), err))?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    let bookmarks = if contents.starts_with("WEBVTT") {
+        parse_vtt(contents)
+    } else {
+        parse_json(contents)
+    };
+    bookmarks
+        .filter(|bookmarks| !bookmarks.is_empty())
+        .ok_or_else(|| MediaPlayerErrors::ErrorInvalidBookmarks(path.to_path_buf()))
+}
+
+/// The bookmarks as a [`JsonBookmarks`] file
+fn to_json(uri: &str, bookmarks: &[Bookmark]) -> String {
+    let file = JsonBookmarks {
+        uri: uri.to_string(),
+        bookmarks: bookmarks
+            .iter()
+            .map(|bookmark| JsonBookmark {
+                position: bookmark.position.mseconds() as f64 / 1000.0,
+                name: bookmark.name.clone(),
+                note: bookmark.note.clone(),
+            })
+            .collect(),
+    };
+    let mut json = serde_json::to_string_pretty(&file).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+/// Each bookmark is a chapter lasting until the next one, its note is written in the
+/// `NOTE` block before it. WebVTT notes cannot hold blank lines nor `-->`, so the blank
+/// lines are dropped and the arrows shortened.
+fn to_vtt(bookmarks: &[Bookmark], duration: Option<ClockTime>) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let end = bookmarks
+            .get(index + 1)
+            .map(|next| next.position)
+            .or(duration)
+            .filter(|end| *end > bookmark.position)
+            .unwrap_or(bookmark.position + LAST_CHAPTER_LENGTH);
+        let note = bookmark
+            .note
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .replace("-->", "->");
+        if !note.is_empty() {
+            vtt.push_str(&format!("\nNOTE\n{}\n", note));
+        }
+        // Chapter titles are a single line
+        let name = bookmark.name.lines().collect::<Vec<_>>().join(" ");
+        vtt.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            index + 1,
+            vtt_timestamp(bookmark.position),
+            vtt_timestamp(end),
+            name
+        ));
+    }
+    vtt
+}
+
+/// `hh:mm:ss.ttt`
+fn vtt_timestamp(time: ClockTime) -> String {
+    let milliseconds = time.mseconds();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+fn parse_vtt(contents: &str) -> Option<Vec<Bookmark>> {
+    let contents = contents.replace("\r\n", "\n");
+    let mut blocks = contents
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|block| !block.is_empty());
+    if !blocks.next()?.starts_with("WEBVTT") {
+        return None;
+    }
+
+    let mut bookmarks = Vec::new();
+    // A `NOTE` block holds the note of the cue following it
+    let mut note = None;
+    for block in blocks {
+        if let Some(text) = block.strip_prefix("NOTE") {
+            note = Some(text.trim().to_string());
+            continue;
+        }
+        // The cue identifier is optional, the timings come first without it
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let Some(timings) = lines.next() else {
+            continue;
+        };
+        let (start, _) = timings.split_once("-->")?;
+        bookmarks.push(Bookmark {
+            position: parse_timestamp(start).ok()?,
+            name: lines.collect::<Vec<_>>().join(" "),
+            note: note.take().unwrap_or_default(),
+        });
+    }
+    Some(bookmarks)
+}
+
+fn parse_json(contents: &str) -> Option<Vec<Bookmark>> {
+    let file = serde_json::from_str::<JsonBookmarks>(contents).ok()?;
+    file.bookmarks
+        .into_iter()
+        .map(|bookmark| {
+            // Bookmarks are kept to the millisecond
+            (bookmark.position.is_finite() && bookmark.position >= 0.0).then(|| Bookmark {
+                position: ClockTime::from_mseconds((bookmark.position * 1000.0).round() as u64),
+                name: bookmark.name,
+                note: bookmark.note,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmarks() -> Vec<Bookmark> {
+        vec![
+            Bookmark {
+                position: ClockTime::ZERO,
+                name: "Intro".to_string(),
+                note: "First line\nsecond --> line".to_string(),
+            },
+            Bookmark {
+                position: ClockTime::from_mseconds(65_500),
+                name: "Part \"2\"".to_string(),
+                note: String::new(),
+            },
+            Bookmark {
+                position: ClockTime::from_mseconds(3_723_042),
+                name: "Multi\nline name".to_string(),
+                note: "a\n\nb\\c".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = to_json("file:///talk.mkv", &bookmarks());
+        assert_eq!(parse_json(&json), Some(bookmarks()));
+        assert_eq!(
+            parse_json(r#"{"bookmarks": [{"position": 1.5}]}"#),
+            Some(vec![Bookmark {
+                position: ClockTime::from_mseconds(1500),
+                name: String::new(),
+                note: String::new(),
+            }])
+        );
+        assert_eq!(parse_json(r#"{"bookmarks": [{"position": -1}]}"#), None);
+        assert_eq!(parse_json("[]"), None);
+    }
+
+    #[test]
+    fn vtt_round_trip() {
+        let vtt = to_vtt(&bookmarks(), Some(ClockTime::from_seconds(4000)));
+        assert!(vtt.contains("\n01:02:03.042 --> 01:06:40.000\n"));
+        // Notes lose their blank lines and arrows, names their line breaks
+        let mut expected = bookmarks();
+        expected[0].note = "First line\nsecond -> line".to_string();
+        expected[2].name = "Multi line name".to_string();
+        expected[2].note = "a\nb\\c".to_string();
+        assert_eq!(parse_vtt(&vtt), Some(expected));
+    }
+
+    #[test]
+    fn watch_later_keeps_the_bookmarks() {
+        let store = Bookmarks::load_from(PathBuf::from("/nonexistent/bookmarks.ini"));
+        let uri = "udp://239.0.0.1:5000";
+        let origin = OriginType::Udp {
+            caps: Some("video/mpegts".to_string()),
+            latency: 300,
+        };
+        store.set_bookmarks(uri, &bookmarks()[..1]);
+        store.set_watch_later(uri, Some("Channel"), &origin, true);
+        store.set_position(uri, Some(ClockTime::from_seconds(42)));
+        store.set_bookmarks(uri, &bookmarks());
+
+        let media = store.watch_later_entry(uri).unwrap();
+        assert_eq!(media.title, "Channel");
+        assert_eq!(media.position, Some(ClockTime::from_seconds(42)));
+        assert!(matches!(
+            media.origin,
+            Some(OriginType::Udp { caps: Some(caps), latency: 300 }) if caps == "video/mpegts"
+        ));
+        assert_eq!(store.bookmarks(uri), bookmarks());
+
+        store.set_watch_later(uri, None, &origin, false);
+        assert!(store.watch_later().is_empty());
+        assert_eq!(store.bookmarks(uri), bookmarks());
+        store.set_bookmarks(uri, &[]);
+        assert!(store.bookmarks(uri).is_empty());
+    }
+}
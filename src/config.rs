@@ -1,28 +1,310 @@
-
-use     clap::{Parser, Subcommand}  ;
+use {
+    crate::{
+        adaptive::QualityLock,
+        decoders::HardwareDecoding,
+        devices,
+        directory::DirectoryScan,
+        encoding::EncodingFormat,
+        playlist::Playlist,
+        recorder::RecordingSettings,
+        transcoder::TranscodeOptions,
+        video_filter::{Deinterlace, DeinterlaceMethod},
+        visualization::Visualization,
+    },
+    clap::{Parser, Subcommand, ValueEnum},
+    gstreamer::{ClockTime, glib::KeyFile},
+    std::path::{Path, PathBuf},
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
-    /// The URI of the media to play 
+    /// The URI of the media to play, export or convert, or the name or index of the capture
+    /// device. Without it, the window opens on the recent media
     #[clap(short, long)]
-    uri: String,
+    uri: Option<String>,
+
+    /// When `--uri` is a directory, also queue the media of its subdirectories
+    #[clap(long)]
+    pub recursive: bool,
+
+    /// When `--uri` is a directory, only queue the files matching this glob, e.g. "*.mkv".
+    /// Can be repeated
+    #[clap(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// When `--uri` is a directory, skip the files and subdirectories matching this glob,
+    /// e.g. "*sample*". Can be repeated
+    #[clap(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// List the capture devices usable with the `device` origin and the audio outputs, and exit
+    #[clap(long)]
+    pub list_devices: bool,
+
+    /// Loudness normalization mode
+    #[clap(long, value_enum, default_value_t = NormalizationMode::Off)]
+    pub normalization: NormalizationMode,
+
+    /// Target loudness used by the normalization, in LUFS
+    #[clap(long, default_value_t = -18.0, allow_negative_numbers = true)]
+    pub target_lufs: f64,
+
+    /// Allow the volume to go up to 200%
+    #[clap(long)]
+    pub volume_boost: bool,
+
+    /// Size of the network buffer, in bytes
+    #[clap(long)]
+    pub buffer_size: Option<i32>,
+
+    /// Duration of the network buffer, in milliseconds
+    #[clap(long)]
+    pub buffer_duration: Option<u64>,
+
+    /// Quality of HLS and DASH streams: auto, a resolution (720p) or a maximum bitrate (2500k)
+    #[clap(long, default_value = "auto")]
+    pub quality: QualityLock,
 
-    /// The origin of the media to play
+    /// Use of the hardware video decoders (VA-API, NVDEC, V4L2...)
+    #[clap(long, value_enum, default_value_t = HardwareDecoding::Auto)]
+    pub hw_decoding: HardwareDecoding,
+
+    /// Audio output playing the media, by name or index (see `--list-devices`)
+    #[clap(long)]
+    pub audio_device: Option<String>,
+
+    /// When the video is deinterlaced. `auto` deinterlaces the media flagged as interlaced
+    #[clap(long, value_enum, default_value_t = Deinterlace::Auto)]
+    pub deinterlace: Deinterlace,
+
+    /// Deinterlacing algorithm
+    #[clap(long, value_enum, default_value_t = DeinterlaceMethod::Greedyh)]
+    pub deinterlace_method: DeinterlaceMethod,
+
+    /// What is shown in place of the picture of audio-only media
+    #[clap(long, value_enum, default_value_t = Visualization::Goom)]
+    pub visualization: Visualization,
+
+    /// Start recording the media as soon as it plays
+    #[clap(long)]
+    pub record: bool,
+
+    /// Format of the recordings. Streams already in this format are saved without re-encoding
+    #[clap(long, value_enum, default_value_t = EncodingFormat::Mkv)]
+    pub record_format: EncodingFormat,
+
+    /// Directory of the recordings, the videos directory by default
+    #[clap(long)]
+    pub record_dir: Option<PathBuf>,
+
+    /// File name of the recordings, without extension. `{title}` is the name of the media,
+    /// `%` sequences are date and time formats
+    #[clap(long, default_value = "{title}-%Y%m%d-%H%M%S")]
+    pub record_template: String,
+
+    /// The origin of the media to play, or a command run without the window
     #[command(subcommand)]
-    origin: OriginType,
+    command: Option<Command>,
 }
 
 impl Args {
-    pub fn formatted_uri(&self) -> String {
-        match self.origin {
-            OriginType::File => format!("file://{}", self.uri),
-            OriginType::Http => self.uri.clone(),
+    /// URI of the media to play, `None` when neither an URI nor an origin is given
+    pub fn formatted_uri(&self) -> Result<Option<String>, String> {
+        let (uri, command) = match (&self.uri, &self.command) {
+            (None, None) => return Ok(None),
+            (Some(uri), Some(command)) => (uri, command),
+            _ => {
+                return Err(
+                    "An URI and an origin (file, http, rtsp, srt, udp or device) are required"
+                        .to_string(),
+                );
+            }
+        };
+        let origin = match command {
+            Command::Origin(origin) => origin,
+            Command::Export(_) | Command::Convert(_) => return input_uri(uri).map(Some),
+        };
+        match origin {
+            OriginType::File => Ok(Some(format!("file://{}", uri))),
+            OriginType::Http
+            | OriginType::Rtsp { .. }
+            | OriginType::Srt { .. }
+            | OriginType::Udp { .. } => Ok(Some(uri.clone())),
+            OriginType::Device => {
+                let device = devices::find_capture_device(uri).ok_or_else(|| {
+                    format!("No capture device named {}, see --list-devices", uri)
+                })?;
+                devices::device_uri(&device)
+                    .map(Some)
+                    .ok_or_else(|| format!("The device {} cannot be played", uri))
+            }
+        }
+    }
+
+    /// Media of `--uri` sorted naturally, when it is a directory opened with the `file`
+    /// origin
+    pub fn directory_queue(&self) -> Option<Result<Playlist, String>> {
+        let (Some(uri), Some(Command::Origin(OriginType::File))) = (&self.uri, &self.command)
+        else {
+            return None;
+        };
+        let directory = Path::new(uri);
+        if !directory.is_dir() {
+            return None;
+        }
+        let scan = DirectoryScan {
+            recursive: self.recursive,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        };
+        Some(match scan.scan(directory) {
+            Ok(playlist) if playlist.entries.is_empty() => {
+                Err(format!("No media found in {}", uri))
+            }
+            Ok(playlist) => Ok(playlist),
+            Err(err) => Err(format!("Unable to read {}: {}", uri, err)),
+        })
+    }
+
+    pub fn origin(&self) -> Option<&OriginType> {
+        match &self.command {
+            Some(Command::Origin(origin)) => Some(origin),
+            _ => None,
+        }
+    }
+
+    /// Output file and options of the `export` and `convert` commands
+    pub fn transcoding(&self) -> Option<(&Path, TranscodeOptions)> {
+        match &self.command {
+            Some(Command::Export(export)) => {
+                let mut options = export.encode.options();
+                options.range = Some((export.from, export.to));
+                Some((&export.encode.output, options))
+            }
+            Some(Command::Convert(convert)) => Some((&convert.output, convert.options())),
+            _ => None,
+        }
+    }
+
+    pub fn recording_settings(&self) -> RecordingSettings {
+        let defaults = RecordingSettings::default();
+        RecordingSettings {
+            format: self.record_format,
+            directory: self.record_dir.clone().unwrap_or(defaults.directory),
+            template: self.record_template.clone(),
         }
     }
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone, Debug)]
+pub enum Command {
+    #[command(flatten)]
+    Origin(OriginType),
+    /// Export the segment of the media between two timestamps to a new file, without
+    /// opening the window
+    #[clap(name = "export")]
+    Export(ExportArgs),
+    /// Convert the media to another format, without opening the window
+    #[clap(name = "convert")]
+    Convert(EncodeArgs),
+}
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct ExportArgs {
+    /// Start of the clip: seconds, mm:ss or hh:mm:ss, with optional fractions of seconds
+    #[clap(long, value_parser = parse_timestamp)]
+    pub from: ClockTime,
+    /// End of the clip, in the same format as --from
+    #[clap(long, value_parser = parse_timestamp)]
+    pub to: ClockTime,
+    #[command(flatten)]
+    pub encode: EncodeArgs,
+}
+
+/// Output of the `export` and `convert` commands
+#[derive(clap::Args, Clone, Debug)]
+pub struct EncodeArgs {
+    /// File to write to
+    #[clap(long)]
+    pub output: PathBuf,
+    /// Format of the output, guessed from its extension by default
+    #[clap(long, value_enum)]
+    pub format: Option<EncodingFormat>,
+    /// Width of the video, the height follows the aspect ratio unless set too
+    #[clap(long)]
+    pub width: Option<u32>,
+    /// Height of the video, the width follows the aspect ratio unless set too
+    #[clap(long)]
+    pub height: Option<u32>,
+    /// Bitrate of the video, in kbps
+    #[clap(long)]
+    pub video_bitrate: Option<u32>,
+    /// Bitrate of the audio, in kbps. Ignored by FLAC, which is lossless
+    #[clap(long)]
+    pub audio_bitrate: Option<u32>,
+}
+
+impl EncodeArgs {
+    pub fn options(&self) -> TranscodeOptions {
+        let format = self
+            .format
+            .or_else(|| EncodingFormat::from_path(&self.output))
+            .unwrap_or(EncodingFormat::Mkv);
+        TranscodeOptions {
+            width: self.width,
+            height: self.height,
+            video_bitrate: self.video_bitrate,
+            audio_bitrate: self.audio_bitrate,
+            ..TranscodeOptions::new(format)
+        }
+    }
+}
+
+/// URI of a media given either as an URI or as a local path
+fn input_uri(input: &str) -> Result<String, String> {
+    if input.contains("://") {
+        return Ok(input.to_string());
+    }
+    let path = Path::new(input)
+        .canonicalize()
+        .map_err(|err| format!("Unable to open {}: {}", input, err))?;
+    gstreamer::glib::filename_to_uri(&path, None)
+        .map(|uri| uri.to_string())
+        .map_err(|err| format!("Unable to open {}: {}", input, err))
+}
+
+/// Parse a timestamp given as seconds (`90.5`), `mm:ss` or `hh:mm:ss`
+pub fn parse_timestamp(value: &str) -> Result<ClockTime, String> {
+    let invalid = || {
+        format!(
+            "Invalid timestamp {}, expected seconds, mm:ss or hh:mm:ss",
+            value
+        )
+    };
+    let parts = value.trim().split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let mut seconds = 0.0;
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+        let number = part.parse::<f64>().map_err(|_| invalid())?;
+        // Only the seconds may have a fraction, and the minutes and seconds stay under 60
+        if number < 0.0 || !number.is_finite() || (!is_last && number.fract() != 0.0) {
+            return Err(invalid());
+        }
+        if index > 0 && number >= 60.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + number;
+    }
+    Ok(ClockTime::from_nseconds(
+        (seconds * 1_000_000_000.0).round() as u64,
+    ))
+}
+
+#[derive(Subcommand, Clone, Debug)]
 pub enum OriginType {
     /// The media is a file
     #[clap(name = "file")]
@@ -30,4 +312,233 @@ pub enum OriginType {
     /// The media is a HTTP stream
     #[clap(name = "http")]
     Http,
-}
\ No newline at end of file
+    /// The media is a RTSP stream (rtsp://host:port/path)
+    #[clap(name = "rtsp")]
+    Rtsp {
+        /// Transport used to receive the media
+        #[clap(long, value_enum, default_value_t = RtspTransport::Tcp)]
+        transport: RtspTransport,
+        /// User name of the RTSP server
+        #[clap(long)]
+        user: Option<String>,
+        /// Password of the RTSP server
+        #[clap(long)]
+        password: Option<String>,
+        /// Latency of the jitter buffer, in milliseconds
+        #[clap(long, default_value_t = 200)]
+        latency: u32,
+    },
+    /// The media is a SRT stream (srt://host:port)
+    #[clap(name = "srt")]
+    Srt {
+        /// Connect to the sender, or wait for it to connect
+        #[clap(long, value_enum, default_value_t = SrtMode::Caller)]
+        mode: SrtMode,
+        /// Passphrase of the encrypted stream
+        #[clap(long)]
+        passphrase: Option<String>,
+        /// Latency of the SRT receiver, in milliseconds
+        #[clap(long, default_value_t = 125)]
+        latency: u32,
+    },
+    /// The media is a capture device, named by the URI (see --list-devices)
+    #[clap(name = "device")]
+    Device,
+    /// The media is a raw UDP or RTP stream (udp://address:port)
+    #[clap(name = "udp")]
+    Udp {
+        /// Caps of the received packets, required for RTP
+        /// e.g. "application/x-rtp,media=video,encoding-name=H264,clock-rate=90000"
+        #[clap(long)]
+        caps: Option<String>,
+        /// Latency of the pipeline, in milliseconds
+        #[clap(long, default_value_t = 200)]
+        latency: u32,
+    },
+}
+
+impl OriginType {
+    /// Live origins cannot be paused to buffer nor seeked
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self,
+            OriginType::Rtsp { .. }
+                | OriginType::Srt { .. }
+                | OriginType::Udp { .. }
+                | OriginType::Device
+        )
+    }
+
+    /// Name of the origin on the command line, e.g. `rtsp`
+    pub fn name(&self) -> &'static str {
+        match self {
+            OriginType::File => "file",
+            OriginType::Http => "http",
+            OriginType::Rtsp { .. } => "rtsp",
+            OriginType::Srt { .. } => "srt",
+            OriginType::Device => "device",
+            OriginType::Udp { .. } => "udp",
+        }
+    }
+
+    /// Origin named `name` with the default options of the command line
+    pub fn with_defaults(name: &str) -> Option<Self> {
+        OriginArgs::try_parse_from(["origin", name])
+            .ok()
+            .map(|args| args.origin)
+    }
+
+    /// Origin of a media opened from its URI alone, with the default options. Capture
+    /// devices are opened by name, so they have none.
+    pub fn for_uri(uri: &str) -> Option<Self> {
+        let (scheme, _) = uri.split_once("://")?;
+        let name = match scheme {
+            "file" => "file",
+            "http" | "https" => "http",
+            "rtsp" | "rtsps" | "rtspt" | "rtspu" => "rtsp",
+            "srt" => "srt",
+            "udp" => "udp",
+            _ => return None,
+        };
+        Self::with_defaults(name)
+    }
+
+    /// Write the origin and its options in the `group` of `key_file`. Passwords and
+    /// passphrases are not written, the origin is only marked as needing one.
+    pub fn store(&self, key_file: &KeyFile, group: &str) {
+        Self::forget(key_file, group);
+        key_file.set_string(group, "origin", self.name());
+        match self {
+            OriginType::File | OriginType::Http | OriginType::Device => {}
+            OriginType::Rtsp {
+                transport,
+                user,
+                password,
+                latency,
+            } => {
+                key_file.set_string(group, "transport", &value_name(transport));
+                if let Some(user) = user {
+                    key_file.set_string(group, "user", user);
+                }
+                key_file.set_boolean(group, "credentials", password.is_some());
+                key_file.set_int64(group, "latency", i64::from(*latency));
+            }
+            OriginType::Srt {
+                mode,
+                passphrase,
+                latency,
+            } => {
+                key_file.set_string(group, "mode", &value_name(mode));
+                key_file.set_boolean(group, "credentials", passphrase.is_some());
+                key_file.set_int64(group, "latency", i64::from(*latency));
+            }
+            OriginType::Udp { caps, latency } => {
+                if let Some(caps) = caps {
+                    key_file.set_string(group, "caps", caps);
+                }
+                key_file.set_int64(group, "latency", i64::from(*latency));
+            }
+        }
+    }
+
+    /// Remove the origin written by [`OriginType::store`]
+    pub fn forget(key_file: &KeyFile, group: &str) {
+        for key in ORIGIN_KEYS {
+            key_file.remove_key(group, key).ok();
+        }
+    }
+
+    /// Origin written by [`OriginType::store`], `None` if there is none or if it needs a
+    /// password or passphrase
+    pub fn load(key_file: &KeyFile, group: &str) -> Option<Self> {
+        if key_file.boolean(group, "credentials").unwrap_or(false) {
+            return None;
+        }
+        let mut origin = Self::with_defaults(&key_file.string(group, "origin").ok()?)?;
+        let string = |key: &str| {
+            key_file
+                .string(group, key)
+                .ok()
+                .map(|value| value.to_string())
+        };
+        let stored_latency = key_file
+            .int64(group, "latency")
+            .ok()
+            .and_then(|latency| u32::try_from(latency).ok());
+        match &mut origin {
+            OriginType::File | OriginType::Http | OriginType::Device => {}
+            OriginType::Rtsp {
+                transport,
+                user,
+                latency,
+                ..
+            } => {
+                if let Some(value) = string("transport") {
+                    *transport = RtspTransport::from_str(&value, true).ok()?;
+                }
+                *user = string("user");
+                *latency = stored_latency.unwrap_or(*latency);
+            }
+            OriginType::Srt { mode, latency, .. } => {
+                if let Some(value) = string("mode") {
+                    *mode = SrtMode::from_str(&value, true).ok()?;
+                }
+                *latency = stored_latency.unwrap_or(*latency);
+            }
+            OriginType::Udp { caps, latency } => {
+                *caps = string("caps");
+                *latency = stored_latency.unwrap_or(*latency);
+            }
+        }
+        Some(origin)
+    }
+}
+
+/// Keys written by [`OriginType::store`]
+const ORIGIN_KEYS: [&str; 7] = [
+    "origin",
+    "transport",
+    "user",
+    "credentials",
+    "latency",
+    "mode",
+    "caps",
+];
+
+/// Parser of an origin alone, to get the defaults of its options
+#[derive(Parser)]
+struct OriginArgs {
+    #[command(subcommand)]
+    origin: OriginType,
+}
+
+/// Name of an option value on the command line, e.g. `tcp`
+fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RtspTransport {
+    Tcp,
+    Udp,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SrtMode {
+    /// Connect to a listening sender
+    Caller,
+    /// Wait for the sender to connect
+    Listener,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationMode {
+    /// Play the media as it is
+    Off,
+    /// Use the track ReplayGain, measuring the loudness when tags are missing
+    Track,
+    /// Use the album ReplayGain, falling back to the track gain
+    Album,
+}
@@ -0,0 +1,205 @@
+use {
+    clap::ValueEnum,
+    gstreamer::{prelude::*, *},
+    std::{collections::HashMap, sync::OnceLock},
+};
+
+/// Name of the application message posted when a video decoder is plugged
+pub const DECODER_SELECTED: &str = "aug-decoder-selected";
+
+/// Name prefixes of hardware decoders whose class does not say so, in older plugins
+const HARDWARE_PREFIXES: [&str; 8] = ["va", "nv", "v4l2", "msdk", "qsv", "d3d11", "vtdec", "amc"];
+
+/// Ranks of the video decoders given by the plugins, before any change
+static PLUGIN_RANKS: OnceLock<HashMap<String, Rank>> = OnceLock::new();
+
+/// Use of the hardware video decoders (VA-API, NVDEC, V4L2...)
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HardwareDecoding {
+    /// Keep the ranks given by the plugins
+    #[default]
+    Auto,
+    /// Use hardware decoders when they can decode the media
+    Prefer,
+    /// Only use software decoders
+    Forbid,
+    /// Only use hardware decoders, media they cannot decode fail to play
+    Require,
+}
+
+/// Video decoder plugged by playbin, see [`watch_decoder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoDecoder {
+    /// Name of the element factory, e.g. `vah264dec` or `avdec_h264`
+    pub name: String,
+    pub hardware: bool,
+}
+
+/// Is the factory a decoder running on dedicated hardware?
+pub fn is_hardware_decoder(factory: &ElementFactory) -> bool {
+    factory.has_type(ElementFactoryType::HARDWARE) || has_hardware_prefix(&factory.name())
+}
+
+fn has_hardware_prefix(name: &str) -> bool {
+    HARDWARE_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+fn video_decoders() -> Vec<ElementFactory> {
+    ElementFactory::factories_with_type(
+        ElementFactoryType::DECODER | ElementFactoryType::MEDIA_VIDEO,
+        Rank::NONE,
+    )
+    .into_iter()
+    .collect()
+}
+
+/// Rank given to the decoder by its plugin
+fn plugin_rank(factory: &ElementFactory) -> Rank {
+    let ranks = PLUGIN_RANKS.get_or_init(|| {
+        video_decoders()
+            .into_iter()
+            .map(|factory| (factory.name().to_string(), factory.rank()))
+            .collect()
+    });
+    ranks
+        .get(factory.name().as_str())
+        .copied()
+        .unwrap_or_else(|| factory.rank())
+}
+
+/// Adjust the ranks of the video decoders so decodebin picks them following `mode`,
+/// starting from the ranks of the plugins so `Auto` restores them. Elements already
+/// created keep their decoder, so this is applied before the media is opened.
+pub fn apply_hardware_decoding(mode: HardwareDecoding) {
+    let decoders = video_decoders();
+    let preferred_rank = preferred_rank(
+        decoders
+            .iter()
+            .filter(|factory| !is_hardware_decoder(factory))
+            .map(plugin_rank),
+    );
+    for factory in decoders {
+        let hardware = is_hardware_decoder(&factory);
+        factory.set_rank(decoder_rank(
+            mode,
+            hardware,
+            plugin_rank(&factory),
+            preferred_rank,
+        ));
+    }
+}
+
+/// Rank above every software decoder, given to the hardware decoders to prefer them
+fn preferred_rank(software_ranks: impl Iterator<Item = Rank>) -> Rank {
+    software_ranks
+        .max()
+        .unwrap_or(Rank::PRIMARY)
+        .max(Rank::PRIMARY)
+        + 1
+}
+
+/// Rank of a decoder following `mode`, from the rank given by its plugin
+fn decoder_rank(
+    mode: HardwareDecoding,
+    hardware: bool,
+    plugin_rank: Rank,
+    preferred_rank: Rank,
+) -> Rank {
+    match mode {
+        HardwareDecoding::Prefer if hardware => preferred_rank,
+        HardwareDecoding::Forbid if hardware => Rank::NONE,
+        HardwareDecoding::Require if !hardware => Rank::NONE,
+        HardwareDecoding::Auto
+        | HardwareDecoding::Prefer
+        | HardwareDecoding::Forbid
+        | HardwareDecoding::Require => plugin_rank,
+    }
+}
+
+/// Decoder decodebin would pick for `caps` with the current ranks, e.g. to check that a
+/// machine without GPU falls back to a software decoder
+pub fn preferred_video_decoder(caps: &Caps) -> Option<ElementFactory> {
+    video_decoders()
+        .into_iter()
+        .filter(|factory| factory.rank() > Rank::NONE && factory.can_sink_any_caps(caps))
+        .max_by_key(|factory| factory.rank())
+}
+
+/// Report the video decoders plugged by playbin. Posted from the decoder as an
+/// application message named [`DECODER_SELECTED`], since this runs on a streaming thread.
+pub fn watch_decoder(element: &Element) {
+    let Some(factory) = element.factory() else {
+        return;
+    };
+    if !factory.has_type(ElementFactoryType::DECODER | ElementFactoryType::MEDIA_VIDEO) {
+        return;
+    }
+    let structure = Structure::builder(DECODER_SELECTED)
+        .field("name", factory.name().as_str())
+        .field("hardware", is_hardware_decoder(&factory))
+        .build();
+    element
+        .post_message(
+            message::Application::builder(structure)
+                .src(element)
+                .build(),
+        )
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hardware_prefixes() {
+        for name in ["vah264dec", "nvh265dec", "v4l2slh264dec", "vtdec_hw"] {
+            assert!(has_hardware_prefix(name), "{}", name);
+        }
+        for name in [
+            "avdec_h264",
+            "openh264dec",
+            "vp9dec",
+            "dav1ddec",
+            "vorbisdec",
+        ] {
+            assert!(!has_hardware_prefix(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn preferred_rank_is_above_the_software_decoders() {
+        let software = [Rank::SECONDARY, Rank::PRIMARY + 10, Rank::MARGINAL];
+        assert_eq!(preferred_rank(software.into_iter()), Rank::PRIMARY + 11);
+        assert_eq!(
+            preferred_rank([Rank::MARGINAL].into_iter()),
+            Rank::PRIMARY + 1
+        );
+        assert_eq!(preferred_rank(std::iter::empty()), Rank::PRIMARY + 1);
+    }
+
+    #[test]
+    fn ranks_follow_the_mode() {
+        let (plugin, preferred) = (Rank::SECONDARY, Rank::PRIMARY + 1);
+        for (mode, hardware, rank) in [
+            (HardwareDecoding::Auto, true, plugin),
+            (HardwareDecoding::Auto, false, plugin),
+            (HardwareDecoding::Prefer, true, preferred),
+            (HardwareDecoding::Prefer, false, plugin),
+            (HardwareDecoding::Forbid, true, Rank::NONE),
+            (HardwareDecoding::Forbid, false, plugin),
+            (HardwareDecoding::Require, true, plugin),
+            (HardwareDecoding::Require, false, Rank::NONE),
+        ] {
+            assert_eq!(
+                decoder_rank(mode, hardware, plugin, preferred),
+                rank,
+                "{:?}, hardware: {}",
+                mode,
+                hardware
+            );
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use gstreamer::{prelude::*, *};
+
+/// Device classes of the capture devices
+pub const CAPTURE_CLASSES: [&str; 2] = ["Video/Source", "Audio/Source"];
+/// Device class of the audio outputs
+pub const AUDIO_SINK_CLASS: &str = "Audio/Sink";
+
+/// Name of the application message posted when an audio output is plugged or unplugged
+pub const AUDIO_DEVICES_CHANGED: &str = "aug-audio-devices-changed";
+
+/// Probe the devices of the given classes once
+pub fn list_devices(classes: &[&str]) -> Vec<Device> {
+    let monitor = DeviceMonitor::new();
+    for class in classes {
+        monitor.add_filter(Some(class), None);
+    }
+    if monitor.start().is_err() {
+        return Vec::new();
+    }
+    let devices = monitor.devices().into_iter().collect();
+    monitor.stop();
+    devices
+}
+
+pub fn list_capture_devices() -> Vec<Device> {
+    list_devices(&CAPTURE_CLASSES)
+}
+
+/// Find a capture device by display name, or by its index in [`list_capture_devices`]
+pub fn find_capture_device(name: &str) -> Option<Device> {
+    find_device(list_capture_devices(), name)
+}
+
+pub fn list_audio_sinks() -> Vec<Device> {
+    list_devices(&[AUDIO_SINK_CLASS])
+}
+
+/// Find an audio output by display name, or by its index in [`list_audio_sinks`]
+pub fn find_audio_sink(name: &str) -> Option<Device> {
+    find_device(list_audio_sinks(), name)
+}
+
+fn find_device(devices: Vec<Device>, name: &str) -> Option<Device> {
+    if let Ok(index) = name.parse::<usize>() {
+        return devices.into_iter().nth(index);
+    }
+    devices
+        .into_iter()
+        .find(|device| device.display_name() == name)
+}
+
+/// Keep watching the audio outputs. Plugged and unplugged outputs are reported on `bus`
+/// as an application message named [`AUDIO_DEVICES_CHANGED`], with the display name of
+/// the unplugged output in its `removed` field. The monitor runs until it is stopped.
+pub fn watch_audio_sinks(bus: &Bus) -> Option<DeviceMonitor> {
+    let monitor = DeviceMonitor::new();
+    monitor.add_filter(Some(AUDIO_SINK_CLASS), None);
+
+    let player_bus = bus.clone();
+    monitor.bus().set_sync_handler(move |_, msg| {
+        let mut structure = Structure::builder(AUDIO_DEVICES_CHANGED);
+        match msg.view() {
+            MessageView::DeviceAdded(_) | MessageView::DeviceChanged(_) => (),
+            MessageView::DeviceRemoved(removed) => {
+                structure = structure.field("removed", removed.device().display_name().as_str());
+            }
+            _ => return BusSyncReply::Drop,
+        }
+        player_bus
+            .post(message::Application::new(structure.build()))
+            .ok();
+        BusSyncReply::Drop
+    });
+
+    monitor.start().ok()?;
+    Some(monitor)
+}
+
+/// URI playbin can open for the device. Only devices whose source element has a URI
+/// handler (e.g. `v4l2src`, `alsasrc`) can be played.
+pub fn device_uri(device: &Device) -> Option<String> {
+    let source = device.create_element(None).ok()?;
+    let handler = source.dynamic_cast_ref::<URIHandler>()?;
+    handler.uri().map(|uri| uri.to_string())
+}
+
+/// Print the capture devices and the audio outputs, for `--list-devices`
+pub fn print_devices() {
+    print_capture_devices();
+    println!();
+    print_audio_sinks();
+}
+
+/// Print the audio outputs usable with `--audio-device`
+pub fn print_audio_sinks() {
+    let devices = list_audio_sinks();
+    if devices.is_empty() {
+        println!("No audio output found");
+    }
+    for (index, device) in devices.iter().enumerate() {
+        println!(
+            "{}: {} [{}]",
+            index,
+            device.display_name(),
+            device.device_class()
+        );
+    }
+}
+
+/// Print the capture devices usable with the `device` origin
+pub fn print_capture_devices() {
+    let devices = list_capture_devices();
+    if devices.is_empty() {
+        println!("No capture device found");
+    }
+    for (index, device) in devices.iter().enumerate() {
+        let playable = if device_uri(device).is_some() {
+            ""
+        } else {
+            " (not playable)"
+        };
+        println!(
+            "{}: {} [{}]{}",
+            index,
+            device.display_name(),
+            device.device_class(),
+            playable
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use {
+    crate::playlist::{Playlist, PlaylistEntry, PlaylistFormat},
+    gtk::{gio, glib},
+    std::{
+        cmp::Ordering,
+        fs,
+        io::{self, Read},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Extensions of the files queued without looking at their content
+const MEDIA_EXTENSIONS: [&str; 26] = [
+    "3gp", "aac", "aiff", "ape", "avi", "flac", "flv", "m2ts", "m4a", "m4v", "mka", "mkv", "mov",
+    "mp3", "mp4", "mpeg", "mpg", "oga", "ogg", "ogv", "opus", "ts", "wav", "webm", "wma", "wmv",
+];
+
+/// Bytes read from the files of unknown extensions to guess their type
+const SNIFF_LENGTH: u64 = 4096;
+
+/// Which files of a directory are queued
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryScan {
+    /// Also queue the media of the subdirectories
+    pub recursive: bool,
+    /// Globs of the file names to queue, every media when empty
+    pub include: Vec<String>,
+    /// Globs of the file and directory names to skip
+    pub exclude: Vec<String>,
+}
+
+impl DirectoryScan {
+    /// Queue the media of `directory`, sorted naturally by path
+    pub fn scan(&self, directory: &Path) -> io::Result<Playlist> {
+        self.scan_tree(directory).map(|(playlist, _)| playlist)
+    }
+
+    /// Queue the media of `directory` like [`DirectoryScan::scan`], and list the
+    /// directories walked, `directory` first
+    pub fn scan_tree(&self, directory: &Path) -> io::Result<(Playlist, Vec<PathBuf>)> {
+        // URIs are only made of absolute paths
+        let directory = directory.canonicalize()?;
+        let mut files = Vec::new();
+        let mut directories = Vec::new();
+        self.scan_into(&directory, &mut files, &mut directories)?;
+        files.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+        let entries = files
+            .iter()
+            .filter_map(|path| glib::filename_to_uri(path, None).ok())
+            .map(|uri| PlaylistEntry::new(uri.as_str()))
+            .collect();
+        Ok((Playlist { entries }, directories))
+    }
+
+    fn scan_into(
+        &self,
+        directory: &Path,
+        files: &mut Vec<PathBuf>,
+        directories: &mut Vec<PathBuf>,
+    ) -> io::Result<()> {
+        directories.push(directory.to_path_buf());
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            // Hidden files are usually thumbnails and metadata, not media
+            if name.starts_with('.') || self.exclude.iter().any(|glob| glob_match(glob, &name)) {
+                continue;
+            }
+
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if self.recursive {
+                    self.scan_into(&path, files, directories)?;
+                }
+            } else if (self.include.is_empty()
+                || self.include.iter().any(|glob| glob_match(glob, &name)))
+                && is_media_file(&path)
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Does the file hold audio or video? Known extensions are trusted, the type of the other
+/// files is guessed from their first bytes. Playlists, sniffed as audio, are left out.
+pub fn is_media_file(path: &Path) -> bool {
+    if PlaylistFormat::from_path(path).is_some() {
+        return false;
+    }
+    let known_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            MEDIA_EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        });
+    if known_extension {
+        return true;
+    }
+
+    let mut data = Vec::new();
+    if fs::File::open(path)
+        .and_then(|file| file.take(SNIFF_LENGTH).read_to_end(&mut data))
+        .is_err()
+    {
+        return false;
+    }
+    let (content_type, _) = gio::content_type_guess(Some(path), data.as_slice());
+    gio::content_type_get_mime_type(&content_type)
+        .is_some_and(|mime| mime.starts_with("video/") || mime.starts_with("audio/"))
+}
+
+/// Compare names with their numbers by value, so that `ep2` comes before `ep10`. Letters
+/// are compared without case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(digit);
+                    }
+                    digits
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                // Leading zeros do not change the value, then the longer number wins
+                let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Match `name` against a shell glob: `*` matches any text, `?` one character and
+/// `[abc]`, `[a-z]` or `[!abc]` one character of a set
+pub fn glob_match(glob: &str, name: &str) -> bool {
+    let (glob, name) = (
+        glob.chars().collect::<Vec<_>>(),
+        name.chars().collect::<Vec<_>>(),
+    );
+    let (mut g, mut n) = (0, 0);
+    // Position after the last `*` and the name position it currently matches up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let step = match glob.get(g) {
+            Some('*') => {
+                backtrack = Some((g + 1, n));
+                g += 1;
+                continue;
+            }
+            Some('?') => Some(g + 1),
+            Some('[') => match_class(&glob, g, name[n]),
+            Some(&literal) => (literal == name[n]).then_some(g + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                g = next;
+                n += 1;
+            }
+            // Let the last `*` swallow one more character
+            (None, Some((after_star, matched))) => {
+                g = after_star;
+                n = matched + 1;
+                backtrack = Some((after_star, matched + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the `[...]` class starting at `start`, returning the position after
+/// the class. An unclosed `[` is a literal.
+fn match_class(glob: &[char], start: usize, c: char) -> Option<usize> {
+    let Some(length) = glob[start + 1..].iter().skip(1).position(|&c| c == ']') else {
+        return (c == '[').then_some(start + 1);
+    };
+    let end = start + 2 + length;
+    let mut class = &glob[start + 1..end];
+    let negated = matches!(class.first(), Some('!' | '^'));
+    if negated {
+        class = &class[1..];
+    }
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    (matched != negated).then_some(end + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec![
+            "ep10.mkv",
+            "Ep2.mkv",
+            "ep1.mkv",
+            "ep02.mkv",
+            "ep2.mkv",
+            "EP001.mkv",
+            "extra.mkv",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "ep1.mkv",
+                "EP001.mkv",
+                "Ep2.mkv",
+                "ep2.mkv",
+                "ep02.mkv",
+                "ep10.mkv",
+                "extra.mkv"
+            ]
+        );
+        assert_eq!(natural_cmp("Season 2", "season 2"), Ordering::Equal);
+        assert_eq!(natural_cmp("track9", "track10"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("B", "a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*.mkv", "movie.mkv"));
+        assert!(!glob_match("*.mkv", "movie.mkv.part"));
+        assert!(glob_match("*sample*", "movie-sample-720p.mp4"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("ep?.mkv", "ep1.mkv"));
+        assert!(!glob_match("ep?.mkv", "ep10.mkv"));
+        assert!(glob_match("[a-c]*", "best.mp3"));
+        assert!(glob_match("[!a-z]*", "01 intro.mp3"));
+        assert!(!glob_match("[!a-z]*", "intro.mp3"));
+        assert!(glob_match("[]x]", "]"));
+        // An unclosed `[` is matched literally
+        assert!(glob_match("track[1", "track[1"));
+        assert!(!glob_match("track[1", "track1"));
+    }
+}
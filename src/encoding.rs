@@ -0,0 +1,162 @@
+use {
+    clap::ValueEnum,
+    gstreamer::{Caps, Element, prelude::*},
+    gstreamer_pbutils::{
+        EncodingAudioProfile, EncodingContainerProfile, EncodingProfile, EncodingVideoProfile,
+        prelude::*,
+    },
+    std::path::Path,
+};
+
+/// Output formats of the recordings, clips and conversions
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingFormat {
+    /// MP4 with H.264 video and AAC audio
+    Mp4,
+    /// Matroska with H.264 video and Opus audio
+    Mkv,
+    /// WebM with VP9 video and Opus audio
+    Webm,
+    /// MP3 audio only
+    Mp3,
+    /// FLAC audio only, lossless
+    Flac,
+    /// Opus audio only, in an Ogg container
+    Opus,
+}
+
+impl EncodingFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EncodingFormat::Mp4 => "mp4",
+            EncodingFormat::Mkv => "mkv",
+            EncodingFormat::Webm => "webm",
+            EncodingFormat::Mp3 => "mp3",
+            EncodingFormat::Flac => "flac",
+            EncodingFormat::Opus => "opus",
+        }
+    }
+
+    /// Format matching the extension of `path`, e.g. `clip.mp4`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::from_str(extension, true).ok()
+    }
+
+    /// Does the format drop the video streams?
+    pub fn is_audio_only(&self) -> bool {
+        matches!(
+            self,
+            EncodingFormat::Mp3 | EncodingFormat::Flac | EncodingFormat::Opus
+        )
+    }
+
+    /// Caps of the container, MP3 and FLAC streams are written as they are
+    fn container_caps(&self) -> Option<Caps> {
+        match self {
+            EncodingFormat::Mp4 => Some(
+                Caps::builder("video/quicktime")
+                    .field("variant", "iso")
+                    .build(),
+            ),
+            EncodingFormat::Mkv => Some(Caps::builder("video/x-matroska").build()),
+            EncodingFormat::Webm => Some(Caps::builder("video/webm").build()),
+            EncodingFormat::Opus => Some(Caps::builder("application/ogg").build()),
+            EncodingFormat::Mp3 | EncodingFormat::Flac => None,
+        }
+    }
+
+    fn video_caps(&self) -> Option<Caps> {
+        match self {
+            EncodingFormat::Mp4 | EncodingFormat::Mkv => {
+                Some(Caps::builder("video/x-h264").build())
+            }
+            EncodingFormat::Webm => Some(Caps::builder("video/x-vp9").build()),
+            EncodingFormat::Mp3 | EncodingFormat::Flac | EncodingFormat::Opus => None,
+        }
+    }
+
+    fn audio_caps(&self) -> Caps {
+        match self {
+            EncodingFormat::Mp4 => Caps::builder("audio/mpeg")
+                .field("mpegversion", 4i32)
+                .build(),
+            EncodingFormat::Mp3 => Caps::builder("audio/mpeg")
+                .field("mpegversion", 1i32)
+                .field("layer", 3i32)
+                .build(),
+            EncodingFormat::Flac => Caps::builder("audio/x-flac").build(),
+            EncodingFormat::Mkv | EncodingFormat::Webm | EncodingFormat::Opus => {
+                Caps::builder("audio/x-opus").build()
+            }
+        }
+    }
+
+    /// Profile for `encodebin`. Streams already encoded in the profile formats are muxed
+    /// as they are, the others are encoded. Presence 0 allows any number of streams of
+    /// each type, so audio-only or video-only media are accepted too.
+    pub fn profile(&self) -> EncodingProfile {
+        self.scaled_profile(None, None)
+    }
+
+    /// Profile for `encodebin` scaling the video to `width` and/or `height`. When only one
+    /// of them is given the other follows the aspect ratio of the media.
+    pub fn scaled_profile(&self, width: Option<u32>, height: Option<u32>) -> EncodingProfile {
+        let audio = EncodingAudioProfile::builder(&self.audio_caps())
+            .presence(0)
+            .build();
+        let Some(container_caps) = self.container_caps() else {
+            return audio.upcast();
+        };
+
+        let mut container = EncodingContainerProfile::builder(&container_caps)
+            .name(self.extension())
+            .add_profile(audio);
+
+        if let Some(video_caps) = self.video_caps() {
+            // Square pixels let the scaler keep the display aspect ratio
+            let restriction = (width.is_some() || height.is_some()).then(|| {
+                let mut restriction = Caps::builder("video/x-raw")
+                    .field("pixel-aspect-ratio", gstreamer::Fraction::new(1, 1));
+                if let Some(width) = width {
+                    restriction = restriction.field("width", width as i32);
+                }
+                if let Some(height) = height {
+                    restriction = restriction.field("height", height as i32);
+                }
+                restriction.build()
+            });
+            let mut video = EncodingVideoProfile::builder(&video_caps).presence(0);
+            if let Some(restriction) = &restriction {
+                video = video.restriction(restriction);
+            }
+            container = container.add_profile(video.build());
+        }
+
+        container.build().upcast()
+    }
+}
+
+/// Set the bitrate of an encoder created by `encodebin`, in kbps. Each encoder has its
+/// own property and unit, unknown encoders keep their defaults.
+pub fn set_encoder_bitrate(encoder: &Element, kbps: u32) {
+    let Some(factory) = encoder.factory() else {
+        return;
+    };
+    let bps = u64::from(kbps) * 1000;
+    let (property, value) = match factory.name().as_str() {
+        "x264enc" | "x265enc" | "nvh264enc" | "vah264enc" | "vaapih264enc" => {
+            ("bitrate", u64::from(kbps))
+        }
+        "lamemp3enc" => {
+            encoder.set_property_from_str("target", "bitrate");
+            ("bitrate", u64::from(kbps))
+        }
+        "vp8enc" | "vp9enc" => ("target-bitrate", bps),
+        "openh264enc" | "opusenc" | "avenc_aac" | "fdkaacenc" | "voaacenc" => ("bitrate", bps),
+        _ => return,
+    };
+    if encoder.has_property(property) {
+        encoder.set_property_from_str(property, &value.to_string());
+    }
+}
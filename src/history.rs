@@ -0,0 +1,183 @@
+use {
+    crate::{
+        config::OriginType,
+        media_player::MediaPlayerErrors,
+        media_settings::{config_dir, save_key_file, uri_group},
+        recorder::media_title,
+    },
+    gstreamer::{
+        ClockTime,
+        glib::{self, KeyFile, KeyFileFlags},
+    },
+    std::{cmp::Reverse, path::PathBuf},
+};
+
+/// Number of media kept in the history
+pub const MAX_RECENT_MEDIA: usize = 20;
+
+/// Group of the history options. URIs are escaped, so no media uses it.
+const OPTIONS_GROUP: &str = "options";
+
+/// A media opened before
+#[derive(Debug, Clone)]
+pub struct RecentMedia {
+    pub uri: String,
+    /// Title tag of the media, or its file name
+    pub title: String,
+    /// Where the playback stopped, `None` if it never started or reached the end
+    pub position: Option<ClockTime>,
+    /// When the media was last opened, in microseconds since the Unix epoch
+    pub opened: i64,
+    /// Origin the media was opened with, `None` if its options were not saved
+    pub origin: Option<OriginType>,
+}
+
+impl RecentMedia {
+    /// Date and time the media was last opened, e.g. `2024-05-14 21:03`
+    pub fn opened_label(&self) -> String {
+        glib::DateTime::from_unix_local_usec(self.opened)
+            .and_then(|opened| opened.format("%Y-%m-%d %H:%M"))
+            .map_or_else(|_| String::new(), |opened| opened.to_string())
+    }
+}
+
+/// Media opened recently, stored as a key file with one group per URI.
+/// Nothing is remembered while the history is disabled.
+pub struct History {
+    path: PathBuf,
+    key_file: KeyFile,
+}
+
+impl History {
+    /// Load the history from `recent.ini` in [`config_dir`]. A missing or unreadable file
+    /// gives an empty history.
+    pub fn load() -> Self {
+        Self::load_from(config_dir().join("recent.ini"))
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let key_file = KeyFile::new();
+        key_file
+            .load_from_file(&path, KeyFileFlags::KEEP_COMMENTS)
+            .ok();
+        Self { path, key_file }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.key_file
+            .boolean(OPTIONS_GROUP, "enabled")
+            .unwrap_or(true)
+    }
+
+    /// Start or stop remembering the opened media. Disabling also clears the history.
+    pub fn set_enabled(&self, enabled: bool) {
+        if !enabled {
+            self.clear();
+        }
+        self.key_file.set_boolean(OPTIONS_GROUP, "enabled", enabled);
+    }
+
+    /// The remembered media, most recent first
+    pub fn entries(&self) -> Vec<RecentMedia> {
+        let mut entries = self
+            .key_file
+            .groups()
+            .iter()
+            .filter(|group| group.as_str() != OPTIONS_GROUP)
+            .filter_map(|group| {
+                let uri = self.key_file.string(group.as_str(), "uri").ok()?;
+                let title = self
+                    .key_file
+                    .string(group.as_str(), "title")
+                    .map_or_else(|_| default_title(&uri), |title| title.to_string());
+                let position = self
+                    .key_file
+                    .int64(group.as_str(), "position")
+                    .ok()
+                    .and_then(|position| u64::try_from(position).ok())
+                    .map(ClockTime::from_mseconds);
+                Some(RecentMedia {
+                    uri: uri.to_string(),
+                    title,
+                    position,
+                    opened: self.key_file.int64(group.as_str(), "opened").unwrap_or(0),
+                    origin: OriginType::load(&self.key_file, group.as_str()),
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| Reverse(entry.opened));
+        entries
+    }
+
+    pub fn entry(&self, uri: &str) -> Option<RecentMedia> {
+        self.entries().into_iter().find(|entry| entry.uri == uri)
+    }
+
+    /// Put the media at the top of the history, forgetting the oldest ones beyond
+    /// [`MAX_RECENT_MEDIA`]. Without `title`, the media is named after its file.
+    pub fn add(&self, uri: &str, title: Option<&str>, origin: &OriginType) {
+        if !self.is_enabled() {
+            return;
+        }
+        let group = uri_group(uri);
+        self.key_file.set_string(&group, "uri", uri);
+        match title {
+            Some(title) => self.key_file.set_string(&group, "title", title),
+            None if !self.key_file.has_key(&group, "title").unwrap_or(false) => {
+                self.key_file
+                    .set_string(&group, "title", &default_title(uri));
+            }
+            None => {}
+        }
+        self.key_file.set_int64(&group, "opened", glib::real_time());
+        origin.store(&self.key_file, &group);
+
+        for entry in self.entries().iter().skip(MAX_RECENT_MEDIA) {
+            self.key_file.remove_group(&uri_group(&entry.uri)).ok();
+        }
+    }
+
+    /// Name the media after its title tag, if it is in the history
+    pub fn set_title(&self, uri: &str, title: &str) {
+        let group = uri_group(uri);
+        if self.key_file.has_group(&group) {
+            self.key_file.set_string(&group, "title", title);
+        }
+    }
+
+    /// Remember where the playback of the media stopped, if it is in the history
+    pub fn set_position(&self, uri: &str, position: Option<ClockTime>) {
+        let group = uri_group(uri);
+        if !self.key_file.has_group(&group) {
+            return;
+        }
+        match position {
+            Some(position) => {
+                self.key_file
+                    .set_int64(&group, "position", position.mseconds() as i64);
+            }
+            None => {
+                self.key_file.remove_key(&group, "position").ok();
+            }
+        }
+    }
+
+    /// Forget every media, keeping the options
+    pub fn clear(&self) {
+        for group in self.key_file.groups().iter() {
+            if group.as_str() != OPTIONS_GROUP {
+                self.key_file.remove_group(group.as_str()).ok();
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), MediaPlayerErrors> {
+        save_key_file(&self.key_file, &self.path)
+    }
+}
+
+/// File name of the media, until its title tag is read
+pub(crate) fn default_title(uri: &str) -> String {
+    let title = media_title(uri);
+    glib::Uri::unescape_string(title.as_str(), None).map_or(title, |title| title.to_string())
+}
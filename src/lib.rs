@@ -1,3 +1,20 @@
 pub mod media_player;
 pub mod ui;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod loudness;
+pub mod audio_filter;
+pub mod bookmarks;
+pub mod adaptive;
+pub mod devices;
+pub mod encoding;
+pub mod recorder;
+pub mod transcoder;
+pub mod decoders;
+pub mod media_settings;
+pub mod history;
+pub mod library;
+pub mod playlist;
+pub mod directory;
+pub mod video_filter;
+pub mod video_transform;
+pub mod visualization;
@@ -0,0 +1,1008 @@
+use {
+    crate::{
+        config::parse_timestamp,
+        directory::{DirectoryScan, is_media_file, natural_cmp},
+        history::default_title,
+        media_player::short_time,
+        media_settings::config_dir,
+    },
+    gstreamer::{bus::BusWatchGuard, prelude::*, *},
+    gstreamer_pbutils::{Discoverer, DiscovererInfo, pb_utils_get_codec_description, prelude::*},
+    gtk::gio,
+    rusqlite::{
+        Connection, Row, functions::FunctionFlags, params, params_from_iter,
+        types::Value as SqlValue,
+    },
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+        rc::Rc,
+        sync::mpsc,
+        thread,
+        time::UNIX_EPOCH,
+    },
+    thiserror::Error,
+};
+
+/// Name of the application message posted for each media indexed by the worker
+const LIBRARY_ITEM_INDEXED: &str = "aug-library-item-indexed";
+/// Name of the application message posted for each indexed media whose file was deleted
+const LIBRARY_ITEM_MISSING: &str = "aug-library-item-missing";
+/// Name of the application message posted for each directory walked by the worker
+const LIBRARY_DIRECTORY_FOUND: &str = "aug-library-directory-found";
+/// Name of the application message posted when an indexing pass finishes
+const LIBRARY_INDEXED: &str = "aug-library-indexed";
+/// Name of the application message posted when a file of a watched folder changes
+const LIBRARY_FILE_CHANGED: &str = "aug-library-file-changed";
+
+/// Time given to the discoverer to read a media
+const DISCOVERER_TIMEOUT: ClockTime = ClockTime::from_seconds(10);
+/// Time given to decode and encode a thumbnail
+const THUMBNAIL_TIMEOUT: ClockTime = ClockTime::from_seconds(5);
+/// Width of the thumbnails, their height follows the aspect ratio
+const THUMBNAIL_WIDTH: i32 = 160;
+
+/// A media of the library, with the metadata read by the discoverer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryItem {
+    pub uri: String,
+    /// Title tag of the media, or its file name
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<ClockTime>,
+    /// Description of the video codec, e.g. `H.264 (High Profile)`
+    pub video_codec: Option<String>,
+    /// Description of the audio codec, e.g. `MPEG-4 AAC`
+    pub audio_codec: Option<String>,
+    /// Width and height of the video
+    pub resolution: Option<(u32, u32)>,
+    /// Frame of the video or cover art, in PNG
+    pub thumbnail: Option<PathBuf>,
+    /// Modification time of the file when it was indexed, in seconds since the Unix epoch
+    pub modified: i64,
+}
+
+impl LibraryItem {
+    /// Artist, album, duration, resolution and codecs, e.g. `Artist · Album · 3:25 · MPEG-1 Layer 3 (MP3)`
+    pub fn details(&self) -> String {
+        let resolution = self
+            .resolution
+            .map(|(width, height)| format!("{}×{}", width, height));
+        [
+            self.artist.clone(),
+            self.album.clone(),
+            self.duration.map(short_time),
+            resolution,
+            self.video_codec.clone(),
+            self.audio_codec.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ")
+    }
+
+    /// Item of a row of the `media` table
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let width = row.get::<_, Option<u32>>("width")?;
+        let height = row.get::<_, Option<u32>>("height")?;
+        Ok(Self {
+            uri: row.get("uri")?,
+            title: row.get("title")?,
+            artist: row.get("artist")?,
+            album: row.get("album")?,
+            duration: row
+                .get::<_, Option<i64>>("duration")?
+                .and_then(|duration| u64::try_from(duration).ok())
+                .map(ClockTime::from_mseconds),
+            video_codec: row.get("video_codec")?,
+            audio_codec: row.get("audio_codec")?,
+            resolution: width.zip(height),
+            thumbnail: row
+                .get::<_, Option<String>>("thumbnail")?
+                .map(PathBuf::from),
+            modified: row.get("modified")?,
+        })
+    }
+
+    /// The item as an application message named [`LIBRARY_ITEM_INDEXED`]
+    fn to_structure(&self) -> Structure {
+        Structure::builder(LIBRARY_ITEM_INDEXED)
+            .field("uri", self.uri.as_str())
+            .field("title", self.title.as_str())
+            .field_if_some("artist", self.artist.as_deref())
+            .field_if_some("album", self.album.as_deref())
+            .field_if_some("duration", self.duration)
+            .field_if_some("video-codec", self.video_codec.as_deref())
+            .field_if_some("audio-codec", self.audio_codec.as_deref())
+            .field_if_some("width", self.resolution.map(|(width, _)| width))
+            .field_if_some("height", self.resolution.map(|(_, height)| height))
+            .field_if_some(
+                "thumbnail",
+                self.thumbnail
+                    .as_ref()
+                    .map(|thumbnail| thumbnail.to_string_lossy().to_string()),
+            )
+            .field("modified", self.modified)
+            .build()
+    }
+
+    /// Read the item posted by the indexing worker
+    pub fn from_structure(structure: &StructureRef) -> Option<Self> {
+        let string = |field| structure.get::<String>(field).ok();
+        let width = structure.get::<u32>("width").ok();
+        let height = structure.get::<u32>("height").ok();
+        Some(Self {
+            uri: string("uri")?,
+            title: string("title")?,
+            artist: string("artist"),
+            album: string("album"),
+            duration: structure.get::<ClockTime>("duration").ok(),
+            video_codec: string("video-codec"),
+            audio_codec: string("audio-codec"),
+            resolution: width.zip(height),
+            thumbnail: string("thumbnail").map(PathBuf::from),
+            modified: structure.get::<i64>("modified").unwrap_or(0),
+        })
+    }
+}
+
+/// A search of the library
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LibraryQuery {
+    /// Words found in the title, the artist or the album
+    pub words: Vec<String>,
+    /// Words found in the title
+    pub title: Vec<String>,
+    /// Words found in the artist
+    pub artist: Vec<String>,
+    /// Words found in the album
+    pub album: Vec<String>,
+    /// Media lasting at least this long
+    pub min_duration: Option<ClockTime>,
+    /// Media lasting at most this long
+    pub max_duration: Option<ClockTime>,
+}
+
+impl LibraryQuery {
+    /// Read a search such as `live artist:queen >5:00`. Words are found in the title, the
+    /// artist or the album, `title:`, `artist:` and `album:` look in one of them only, and
+    /// `>` and `<` bound the duration, in seconds, mm:ss or hh:mm:ss. Words are matched
+    /// without case, and every one of them must be found.
+    pub fn parse(search: &str) -> Self {
+        let mut query = Self::default();
+        for word in search.split_whitespace() {
+            if let Some(duration) = word.strip_prefix('>')
+                && let Ok(duration) = parse_timestamp(duration)
+            {
+                query.min_duration = Some(duration);
+            } else if let Some(duration) = word.strip_prefix('<')
+                && let Ok(duration) = parse_timestamp(duration)
+            {
+                query.max_duration = Some(duration);
+            } else if let Some((field, value)) = word.split_once(':')
+                && !value.is_empty()
+                && let Some(words) = match field.to_lowercase().as_str() {
+                    "title" => Some(&mut query.title),
+                    "artist" => Some(&mut query.artist),
+                    "album" => Some(&mut query.album),
+                    _ => None,
+                }
+            {
+                words.push(value.to_lowercase());
+            } else {
+                query.words.push(word.to_lowercase());
+            }
+        }
+        query
+    }
+
+    /// SQL condition selecting the media of the `media` table matching the query, with
+    /// its parameters. Text is compared with `fold`, the lowercase of the column.
+    fn condition(&self) -> (String, Vec<SqlValue>) {
+        let mut conditions = vec!["1".to_string()];
+        let mut values = Vec::new();
+        for word in &self.words {
+            conditions.push(
+                "(instr(fold(title), ?) OR instr(fold(artist), ?) OR instr(fold(album), ?))"
+                    .to_string(),
+            );
+            values.extend([0; 3].map(|_| SqlValue::Text(word.clone())));
+        }
+        for (column, words) in [
+            ("title", &self.title),
+            ("artist", &self.artist),
+            ("album", &self.album),
+        ] {
+            for word in words {
+                conditions.push(format!("instr(fold({}), ?)", column));
+                values.push(SqlValue::Text(word.clone()));
+            }
+        }
+        if let Some(min) = self.min_duration {
+            conditions.push("duration >= ?".to_string());
+            values.push(SqlValue::Integer(min.mseconds() as i64));
+        }
+        if let Some(max) = self.max_duration {
+            conditions.push("duration <= ?".to_string());
+            values.push(SqlValue::Integer(max.mseconds() as i64));
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+/// Media and folders of the library, stored in a SQLite database
+struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    fn open(path: &Path) -> rusqlite::Result<Self> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).ok();
+        }
+        Self::setup(Connection::open(path)?)
+    }
+
+    fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::setup(Connection::open_in_memory()?)
+    }
+
+    /// Create the tables, and the `fold` function and `natural_order` collation used by the
+    /// searches
+    fn setup(connection: Connection) -> rusqlite::Result<Self> {
+        connection.create_scalar_function(
+            "fold",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |context| {
+                Ok(context
+                    .get::<Option<String>>(0)?
+                    .map(|text| text.to_lowercase()))
+            },
+        )?;
+        connection.create_collation("natural_order", natural_cmp)?;
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             CREATE TABLE IF NOT EXISTS folders (path TEXT PRIMARY KEY);
+             CREATE TABLE IF NOT EXISTS media (
+                 uri TEXT PRIMARY KEY,
+                 title TEXT NOT NULL,
+                 artist TEXT,
+                 album TEXT,
+                 duration INTEGER,
+                 video_codec TEXT,
+                 audio_codec TEXT,
+                 width INTEGER,
+                 height INTEGER,
+                 thumbnail TEXT,
+                 modified INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS media_title ON media (title);
+             CREATE INDEX IF NOT EXISTS media_artist ON media (artist);
+             CREATE INDEX IF NOT EXISTS media_album ON media (album);
+             CREATE INDEX IF NOT EXISTS media_duration ON media (duration);",
+        )?;
+        Ok(Self { connection })
+    }
+
+    /// Folders indexed with their subdirectories, in the order they were added
+    fn folders(&self) -> rusqlite::Result<Vec<PathBuf>> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT path FROM folders ORDER BY rowid")?;
+        statement
+            .query_map([], |row| row.get::<_, String>(0).map(PathBuf::from))?
+            .collect()
+    }
+
+    /// Add a folder, `false` if it is already in the library
+    fn add_folder(&self, folder: &Path) -> rusqlite::Result<bool> {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO folders (path) VALUES (?)",
+                [folder.to_string_lossy()],
+            )
+            .map(|inserted| inserted > 0)
+    }
+
+    fn remove_folder(&self, folder: &Path) -> rusqlite::Result<()> {
+        self.connection
+            .execute(
+                "DELETE FROM folders WHERE path = ?",
+                [folder.to_string_lossy()],
+            )
+            .map(|_| ())
+    }
+
+    /// Media matching `query`, sorted naturally by title
+    fn search(&self, query: &LibraryQuery) -> rusqlite::Result<Vec<LibraryItem>> {
+        let (condition, values) = query.condition();
+        let mut statement = self.connection.prepare_cached(&format!(
+            "SELECT * FROM media WHERE {} ORDER BY title COLLATE natural_order",
+            condition
+        ))?;
+        statement
+            .query_map(params_from_iter(values), LibraryItem::from_row)?
+            .collect()
+    }
+
+    /// Add a media, or replace it once indexed again
+    fn insert(&self, item: &LibraryItem) -> rusqlite::Result<()> {
+        let mut statement = self.connection.prepare_cached(
+            "INSERT OR REPLACE INTO media (uri, title, artist, album, duration, video_codec,
+                 audio_codec, width, height, thumbnail, modified)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        statement.execute(params![
+            item.uri,
+            item.title,
+            item.artist,
+            item.album,
+            item.duration.map(|duration| duration.mseconds() as i64),
+            item.video_codec,
+            item.audio_codec,
+            item.resolution.map(|(width, _)| width),
+            item.resolution.map(|(_, height)| height),
+            item.thumbnail
+                .as_ref()
+                .map(|thumbnail| thumbnail.to_string_lossy().to_string()),
+            item.modified,
+        ])?;
+        Ok(())
+    }
+
+    /// URIs and modification times of the media at `uri`, or inside it if it is a
+    /// directory
+    fn modification_times(&self, uri: &str) -> rusqlite::Result<HashMap<String, i64>> {
+        let (first, last) = directory_range(uri);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT uri, modified FROM media WHERE uri = ? OR (uri >= ? AND uri < ?)",
+        )?;
+        statement
+            .query_map(params![uri, first, last], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect()
+    }
+
+    /// Forget the media at `uri`, or the media inside it if it is a directory, with their
+    /// thumbnails. The media inside the `kept` directories are kept. Returns how many
+    /// media were forgotten.
+    fn remove(&self, uri: &str, kept: &[String]) -> rusqlite::Result<usize> {
+        let (first, last) = directory_range(uri);
+        let mut statement = self.connection.prepare_cached(
+            "SELECT uri, thumbnail FROM media WHERE uri = ? OR (uri >= ? AND uri < ?)",
+        )?;
+        let removed = statement
+            .query_map(params![uri, first, last], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|(removed, _)| {
+                !kept
+                    .iter()
+                    .any(|kept| removed.starts_with(&directory_range(kept).0))
+            })
+            .collect::<Vec<_>>();
+
+        let transaction = self.connection.unchecked_transaction()?;
+        for (uri, thumbnail) in &removed {
+            transaction.execute("DELETE FROM media WHERE uri = ?", [uri])?;
+            if let Some(thumbnail) = thumbnail {
+                fs::remove_file(thumbnail).ok();
+            }
+        }
+        transaction.commit()?;
+        Ok(removed.len())
+    }
+}
+
+/// Bounds of the URIs inside the directory at `uri`: from `<uri>/` included to `<uri>0`
+/// excluded, `0` following `/`
+fn directory_range(uri: &str) -> (String, String) {
+    let uri = uri.trim_end_matches('/');
+    (format!("{}/", uri), format!("{}0", uri))
+}
+
+/// Work of the indexing worker
+struct IndexRequest {
+    /// Files and directories to index, directories with their subdirectories
+    paths: Vec<PathBuf>,
+    /// Modification time of the media of `paths` already indexed, by URI. The unchanged
+    /// files are skipped and the deleted ones forgotten.
+    known: HashMap<String, i64>,
+}
+
+/// Media of the library folders, indexed in the background and watched for changes.
+/// The messages of the indexing worker and of the watches go through the library's own
+/// bus, handled on the main loop.
+pub struct Library {
+    database: Database,
+    /// Requests of the indexing worker
+    indexer: mpsc::Sender<IndexRequest>,
+    bus: Bus,
+    /// Removes the watch of the bus once the library is dropped
+    _bus_watch: Option<BusWatchGuard>,
+    /// Watches of the folders and their subdirectories, by directory
+    monitors: HashMap<PathBuf, gio::FileMonitor>,
+    /// Indexing requests not finished yet
+    indexing: u32,
+    listeners: Rc<RefCell<Vec<Box<dyn Fn()>>>>,
+}
+
+pub type LibraryRef = Rc<RefCell<Library>>;
+
+#[derive(Error, Debug)]
+pub enum LibraryErrors {
+    #[error("Unable to update the library: {0}")]
+    ErrorUpdating(rusqlite::Error),
+}
+
+impl Library {
+    /// Open the library stored in `library.db` in [`config_dir`], and index the files
+    /// changed while the player was closed. The library is kept in memory when the file
+    /// cannot be opened.
+    pub fn open() -> LibraryRef {
+        let path = config_dir().join("library.db");
+        let database = Database::open(&path).or_else(|err| {
+            println!("Unable to open the library {}: {}", path.display(), err);
+            Database::open_in_memory()
+        });
+        let database = database.expect("Unable to create the library");
+
+        let bus = Bus::new();
+        let (indexer, requests) = mpsc::channel();
+        let worker_bus = bus.clone();
+        thread::spawn(move || run_indexer(requests, worker_bus));
+
+        let library = Rc::new(RefCell::new(Self {
+            database,
+            indexer,
+            bus,
+            _bus_watch: None,
+            monitors: HashMap::new(),
+            indexing: 0,
+            listeners: Rc::new(RefCell::new(Vec::new())),
+        }));
+        let library_weak = Rc::downgrade(&library);
+        let bus_watch = library
+            .borrow()
+            .bus
+            .add_watch_local(move |_, msg| {
+                if let Some(library) = library_weak.upgrade() {
+                    library.borrow_mut().handle_message(msg);
+                }
+                glib::ControlFlow::Continue
+            })
+            .expect("Failed to add the library bus watch");
+        library.borrow_mut()._bus_watch = Some(bus_watch);
+        library.borrow_mut().rescan();
+        library
+    }
+
+    /// Register a callback run when media or folders are added or removed, or an indexing
+    /// pass starts or finishes. Callbacks run from the main loop once the library is no
+    /// longer borrowed.
+    pub fn connect_changed(&self, callback: impl Fn() + 'static) {
+        self.listeners.borrow_mut().push(Box::new(callback));
+    }
+
+    fn emit_changed(&self) {
+        let listeners = self.listeners.clone();
+        glib::idle_add_local_once(move || {
+            for listener in listeners.borrow().iter() {
+                listener();
+            }
+        });
+    }
+
+    /// Folders indexed with their subdirectories
+    pub fn folders(&self) -> Vec<PathBuf> {
+        self.database.folders().unwrap_or_else(|err| {
+            println!("Unable to read the library: {}", err);
+            Vec::new()
+        })
+    }
+
+    /// Is the library being indexed in the background?
+    pub fn is_indexing(&self) -> bool {
+        self.indexing > 0
+    }
+
+    /// Media matching `search`, see [`LibraryQuery::parse`], sorted naturally by title
+    pub fn search(&self, search: &str) -> Vec<LibraryItem> {
+        self.database
+            .search(&LibraryQuery::parse(search))
+            .unwrap_or_else(|err| {
+                println!("Unable to search the library: {}", err);
+                Vec::new()
+            })
+    }
+
+    /// Index a folder with its subdirectories, and watch it for changes
+    pub fn add_folder(&mut self, folder: &Path) -> Result<(), LibraryErrors> {
+        if self
+            .database
+            .add_folder(folder)
+            .map_err(LibraryErrors::ErrorUpdating)?
+        {
+            self.index(vec![folder.to_path_buf()]);
+        }
+        Ok(())
+    }
+
+    /// Stop indexing a folder, forgetting its media unless another folder holds them
+    pub fn remove_folder(&mut self, folder: &Path) -> Result<(), LibraryErrors> {
+        self.database
+            .remove_folder(folder)
+            .map_err(LibraryErrors::ErrorUpdating)?;
+        let kept = self.folders();
+        self.monitors.retain(|directory, monitor| {
+            let watched = !directory.starts_with(folder)
+                || kept.iter().any(|kept| directory.starts_with(kept));
+            if !watched {
+                monitor.cancel();
+            }
+            watched
+        });
+        if let Ok(uri) = glib::filename_to_uri(folder, None) {
+            let kept = kept
+                .iter()
+                .filter_map(|kept| glib::filename_to_uri(kept, None).ok())
+                .map(|kept| kept.to_string())
+                .collect::<Vec<_>>();
+            self.database
+                .remove(&uri, &kept)
+                .map_err(LibraryErrors::ErrorUpdating)?;
+        }
+        self.emit_changed();
+        Ok(())
+    }
+
+    /// Index the new and changed media of the library folders, forgetting the deleted ones
+    pub fn rescan(&mut self) {
+        let folders = self.folders();
+        if !folders.is_empty() {
+            self.index(folders);
+        }
+    }
+
+    /// Ask the indexing worker to index `paths`, directories with their subdirectories
+    fn index(&mut self, paths: Vec<PathBuf>) {
+        let mut known = HashMap::new();
+        for path in &paths {
+            let Ok(uri) = glib::filename_to_uri(path, None) else {
+                continue;
+            };
+            match self.database.modification_times(&uri) {
+                Ok(times) => known.extend(times),
+                Err(err) => println!("Unable to read the library: {}", err),
+            }
+        }
+        if self.indexer.send(IndexRequest { paths, known }).is_ok() {
+            self.indexing += 1;
+            self.emit_changed();
+        }
+    }
+
+    /// Forget the media at `uri`, or inside it, and stop watching the directories inside it
+    fn remove(&mut self, uri: &str) -> usize {
+        if let Ok((path, _)) = glib::filename_from_uri(uri) {
+            self.monitors.retain(|directory, monitor| {
+                let watched = !directory.starts_with(&path);
+                if !watched {
+                    monitor.cancel();
+                }
+                watched
+            });
+        }
+        self.database.remove(uri, &[]).unwrap_or_else(|err| {
+            println!("Unable to update the library: {}", err);
+            0
+        })
+    }
+
+    /// Watch a directory of the library for created, changed and deleted files, posted on
+    /// the bus as an application message named [`LIBRARY_FILE_CHANGED`], with the `uri`
+    /// of the file and a `removed` flag. File monitors do not report the changes of the
+    /// subdirectories, each one has its own.
+    fn watch(&mut self, directory: PathBuf) {
+        if self.monitors.contains_key(&directory) {
+            return;
+        }
+        let monitor = gio::File::for_path(&directory).monitor_directory(
+            gio::FileMonitorFlags::WATCH_MOVES,
+            None::<&gio::Cancellable>,
+        );
+        match monitor {
+            Ok(monitor) => {
+                let bus = self.bus.clone();
+                monitor.connect_changed(move |_, file, other_file, event| {
+                    post_file_change(&bus, file, other_file, event);
+                });
+                self.monitors.insert(directory, monitor);
+            }
+            Err(err) => println!("Unable to watch {}: {}", directory.display(), err),
+        }
+    }
+
+    fn handle_message(&mut self, msg: &Message) {
+        let MessageView::Application(application) = msg.view() else {
+            return;
+        };
+        let Some(structure) = application.structure() else {
+            return;
+        };
+        if structure.has_name(LIBRARY_ITEM_INDEXED) {
+            // Shown once the indexing pass finishes
+            if let Some(item) = LibraryItem::from_structure(structure)
+                && let Err(err) = self.database.insert(&item)
+            {
+                println!("Unable to update the library: {}", err);
+            }
+        } else if structure.has_name(LIBRARY_ITEM_MISSING)
+            && let Ok(uri) = structure.get::<&str>("uri")
+        {
+            self.remove(uri);
+        } else if structure.has_name(LIBRARY_DIRECTORY_FOUND)
+            && let Ok(path) = structure.get::<&str>("path")
+        {
+            self.watch(PathBuf::from(path));
+        } else if structure.has_name(LIBRARY_INDEXED) {
+            self.indexing = self.indexing.saturating_sub(1);
+            self.emit_changed();
+        } else if structure.has_name(LIBRARY_FILE_CHANGED)
+            && let (Ok(uri), Ok(removed)) = (
+                structure.get::<&str>("uri"),
+                structure.get::<bool>("removed"),
+            )
+        {
+            if removed {
+                if self.remove(uri) > 0 {
+                    self.emit_changed();
+                }
+            } else if let Ok((path, _)) = glib::filename_from_uri(uri) {
+                self.index(vec![path]);
+            }
+        }
+    }
+}
+
+fn post_file_change(
+    bus: &Bus,
+    file: &gio::File,
+    other_file: Option<&gio::File>,
+    event: gio::FileMonitorEvent,
+) {
+    let changes = match event {
+        // Written files are indexed once complete, created directories right away
+        gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::MovedIn => {
+            vec![(file, false)]
+        }
+        gio::FileMonitorEvent::Created
+            if file.query_file_type(gio::FileQueryInfoFlags::NONE, None::<&gio::Cancellable>)
+                == gio::FileType::Directory =>
+        {
+            vec![(file, false)]
+        }
+        gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => vec![(file, true)],
+        gio::FileMonitorEvent::Renamed => {
+            let mut changes = vec![(file, true)];
+            changes.extend(other_file.map(|other_file| (other_file, false)));
+            changes
+        }
+        _ => return,
+    };
+    for (file, removed) in changes {
+        let structure = Structure::builder(LIBRARY_FILE_CHANGED)
+            .field("uri", file.uri().as_str())
+            .field("removed", removed)
+            .build();
+        bus.post(message::Application::new(structure)).ok();
+    }
+}
+
+/// Index the media of the requests until the library is dropped: directories are scanned
+/// with their subdirectories, posted on `bus` as application messages named
+/// [`LIBRARY_DIRECTORY_FOUND`], and the files whose modification time is the known one
+/// are skipped. Each media is posted as a message named [`LIBRARY_ITEM_INDEXED`], read by
+/// [`LibraryItem::from_structure`], each deleted media as [`LIBRARY_ITEM_MISSING`], then a
+/// message named [`LIBRARY_INDEXED`] carries the number of media indexed.
+fn run_indexer(requests: mpsc::Receiver<IndexRequest>, bus: Bus) {
+    let post = |structure: Structure| {
+        bus.post(message::Application::new(structure)).ok();
+    };
+    let post_indexed = |indexed: u32| {
+        post(
+            Structure::builder(LIBRARY_INDEXED)
+                .field("indexed", indexed)
+                .build(),
+        );
+    };
+    let discoverer = match Discoverer::new(DISCOVERER_TIMEOUT) {
+        Ok(discoverer) => discoverer,
+        Err(err) => {
+            println!("Unable to index the library: {}", err);
+            for _ in requests {
+                post_indexed(0);
+            }
+            return;
+        }
+    };
+    let scan = DirectoryScan {
+        recursive: true,
+        ..Default::default()
+    };
+
+    for IndexRequest { paths, known } in requests {
+        for uri in known.keys() {
+            if glib::filename_from_uri(uri).is_ok_and(|(path, _)| !path.exists()) {
+                post(
+                    Structure::builder(LIBRARY_ITEM_MISSING)
+                        .field("uri", uri.as_str())
+                        .build(),
+                );
+            }
+        }
+
+        let mut indexed = 0u32;
+        for path in paths {
+            let files = if path.is_dir() {
+                match scan.scan_tree(&path) {
+                    Ok((playlist, directories)) => {
+                        for directory in directories {
+                            post(
+                                Structure::builder(LIBRARY_DIRECTORY_FOUND)
+                                    .field("path", directory.to_string_lossy().as_ref())
+                                    .build(),
+                            );
+                        }
+                        playlist
+                            .entries
+                            .iter()
+                            .filter_map(|entry| glib::filename_from_uri(&entry.uri).ok())
+                            .map(|(file, _)| file)
+                            .collect()
+                    }
+                    Err(err) => {
+                        println!("Unable to index {}: {}", path.display(), err);
+                        continue;
+                    }
+                }
+            } else if path.is_file() && is_media_file(&path) {
+                vec![path]
+            } else {
+                continue;
+            };
+
+            for file in files {
+                let Ok(uri) = glib::filename_to_uri(&file, None) else {
+                    continue;
+                };
+                let modified = modification_time(&file);
+                if known.get(uri.as_str()) == Some(&modified) {
+                    continue;
+                }
+                match index(&discoverer, &uri, modified) {
+                    Ok(item) => {
+                        post(item.to_structure());
+                        indexed += 1;
+                    }
+                    Err(err) => println!("Unable to index {}: {}", file.display(), err),
+                }
+            }
+        }
+        post_indexed(indexed);
+    }
+}
+
+fn modification_time(path: &Path) -> i64 {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_secs() as i64)
+}
+
+/// Read the metadata of a media and save its thumbnail
+fn index(discoverer: &Discoverer, uri: &str, modified: i64) -> Result<LibraryItem, glib::Error> {
+    let info = discoverer.discover_uri(uri)?;
+    let tag_list = info.tags();
+    let tag = |read: fn(&TagListRef) -> Option<String>| tag_list.as_deref().and_then(read);
+    let video = info.video_streams().into_iter().next();
+    let audio = info.audio_streams().into_iter().next();
+    let codec =
+        |caps: Option<Caps>| caps.map(|caps| pb_utils_get_codec_description(&caps).to_string());
+
+    Ok(LibraryItem {
+        uri: uri.to_string(),
+        title: tag(|tag_list| Some(tag_list.get::<tags::Title>()?.get().to_string()))
+            .unwrap_or_else(|| default_title(uri)),
+        artist: tag(|tag_list| Some(tag_list.get::<tags::Artist>()?.get().to_string())),
+        album: tag(|tag_list| Some(tag_list.get::<tags::Album>()?.get().to_string())),
+        duration: info.duration(),
+        video_codec: codec(video.as_ref().and_then(|video| video.caps())),
+        audio_codec: codec(audio.as_ref().and_then(|audio| audio.caps())),
+        resolution: video.as_ref().map(|video| (video.width(), video.height())),
+        thumbnail: save_thumbnail(&info, uri),
+        modified,
+    })
+}
+
+/// Where the thumbnail of `uri` is cached, e.g.
+/// `~/.cache/aug-media-player/thumbnails/<md5 of the uri>.png`
+fn thumbnail_path(uri: &str) -> Option<PathBuf> {
+    let checksum = glib::compute_checksum_for_string(glib::ChecksumType::Md5, uri)?;
+    Some(
+        glib::user_cache_dir()
+            .join("aug-media-player")
+            .join("thumbnails")
+            .join(format!("{}.png", checksum)),
+    )
+}
+
+/// Save the cover art of the media, or else a frame of its video, as a PNG thumbnail
+fn save_thumbnail(info: &DiscovererInfo, uri: &str) -> Option<PathBuf> {
+    let cover_art = info.tags().and_then(|tag_list| {
+        tag_list
+            .get::<tags::Image>()
+            .or_else(|| tag_list.get::<tags::PreviewImage>())
+            .map(|image| image.get())
+    });
+    let sample = match cover_art {
+        Some(sample) => sample,
+        None if !info.video_streams().is_empty() => video_frame(uri, info.duration())?,
+        None => return None,
+    };
+
+    let caps = Caps::builder("image/png")
+        .field("width", THUMBNAIL_WIDTH)
+        .build();
+    let thumbnail = gstreamer_video::convert_sample(&sample, &caps, THUMBNAIL_TIMEOUT).ok()?;
+    let map = thumbnail.buffer()?.map_readable().ok()?;
+    let path = thumbnail_path(uri)?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    fs::write(&path, map.as_slice()).ok()?;
+    Some(path)
+}
+
+/// Decode a frame at a tenth of the video, past the black frames of its opening
+fn video_frame(uri: &str, duration: Option<ClockTime>) -> Option<Sample> {
+    let pipeline = parse::launch("uridecodebin name=source ! videoconvert ! fakesink name=sink")
+        .ok()?
+        .downcast::<Bin>()
+        .ok()?;
+    pipeline.by_name("source")?.set_property("uri", uri);
+    let frame = preroll_frame(&pipeline, duration);
+    pipeline.set_state(State::Null).ok();
+    frame
+}
+
+fn preroll_frame(pipeline: &Bin, duration: Option<ClockTime>) -> Option<Sample> {
+    pipeline.set_state(State::Paused).ok()?;
+    pipeline.state(THUMBNAIL_TIMEOUT).0.ok()?;
+    if let Some(duration) = duration {
+        pipeline
+            .seek_simple(SeekFlags::FLUSH | SeekFlags::KEY_UNIT, duration / 10)
+            .ok()?;
+        pipeline.state(THUMBNAIL_TIMEOUT).0.ok()?;
+    }
+    pipeline
+        .by_name("sink")?
+        .property::<Option<Sample>>("last-sample")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(uri: &str, title: &str, artist: Option<&str>, seconds: Option<u64>) -> LibraryItem {
+        LibraryItem {
+            uri: uri.to_string(),
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            duration: seconds.map(ClockTime::from_seconds),
+            modified: 1,
+            ..Default::default()
+        }
+    }
+
+    fn database() -> Database {
+        let database = Database::open_in_memory().unwrap();
+        for item in [
+            item(
+                "file:///music/queen/live.flac",
+                "Live Aid",
+                Some("Queen"),
+                Some(1320),
+            ),
+            item(
+                "file:///music/queen/track10.mp3",
+                "Track 10",
+                Some("Queen"),
+                Some(200),
+            ),
+            item(
+                "file:///music/queen/best/track2.mp3",
+                "Track 2",
+                Some("Queen"),
+                Some(180),
+            ),
+            item("file:///music/other/éclair.ogg", "Éclair", None, None),
+            item("file:///music/queen0.mp3", "Beside", None, Some(60)),
+        ] {
+            database.insert(&item).unwrap();
+        }
+        database
+    }
+
+    fn titles(items: Vec<LibraryItem>) -> Vec<String> {
+        items.into_iter().map(|item| item.title).collect()
+    }
+
+    #[test]
+    fn search() {
+        let database = database();
+        let search = |search: &str| titles(database.search(&LibraryQuery::parse(search)).unwrap());
+        assert_eq!(
+            search(""),
+            ["Beside", "Live Aid", "Track 2", "Track 10", "Éclair"]
+        );
+        assert_eq!(search("QUEEN track"), ["Track 2", "Track 10"]);
+        assert_eq!(search("artist:queen >3:10"), ["Live Aid", "Track 10"]);
+        assert_eq!(search("<1:00"), ["Beside"]);
+        assert_eq!(search("title:éCLAIR"), ["Éclair"]);
+        assert_eq!(search("album:anything"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn insert_replaces_the_media() {
+        let database = database();
+        let mut renamed = item("file:///music/queen0.mp3", "Renamed", None, None);
+        renamed.resolution = Some((1920, 1080));
+        database.insert(&renamed).unwrap();
+        let found = database.search(&LibraryQuery::parse("renamed")).unwrap();
+        assert_eq!(found, [renamed]);
+        assert_eq!(database.search(&LibraryQuery::default()).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn remove_directories() {
+        let database = database();
+        let times = database.modification_times("file:///music/queen/").unwrap();
+        assert_eq!(times.len(), 3);
+        // `queen0.mp3` is next to the directory, not inside it
+        assert_eq!(
+            database
+                .remove("file:///music/queen", &["file:///music/queen/best".into()])
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            titles(database.search(&LibraryQuery::default()).unwrap()),
+            ["Beside", "Track 2", "Éclair"]
+        );
+        assert_eq!(
+            database
+                .remove("file:///music/other/éclair.ogg", &[])
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn folders() {
+        let database = database();
+        assert!(database.add_folder(Path::new("/music")).unwrap());
+        assert!(database.add_folder(Path::new("/videos")).unwrap());
+        assert!(!database.add_folder(Path::new("/music")).unwrap());
+        database.remove_folder(Path::new("/music")).unwrap();
+        assert_eq!(database.folders().unwrap(), [PathBuf::from("/videos")]);
+    }
+}
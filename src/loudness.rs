@@ -0,0 +1,103 @@
+use {
+    crate::{media_player::MediaPlayerErrors, transcoder},
+    gstreamer::{prelude::*, *},
+    std::thread,
+};
+
+/// Name of the application message posted when a loudness analysis pass finishes
+pub const LOUDNESS_MEASURED: &str = "aug-loudness-measured";
+
+/// ReplayGain 2.0 reference loudness, in LUFS
+const REPLAYGAIN_REFERENCE_LUFS: f64 = -18.0;
+
+/// Extra gain to apply on top of the ReplayGain values so tracks play at `target_lufs`
+pub fn pre_amp_for_target(target_lufs: f64) -> f64 {
+    target_lufs - REPLAYGAIN_REFERENCE_LUFS
+}
+
+/// Measure the track gain of `uri` in a background thread with `rganalysis`.
+/// The result is posted on `bus` as an application message named [`LOUDNESS_MEASURED`]
+/// carrying the `uri` and either the measured `gain` in dB, relative to the ReplayGain
+/// reference level, or the `error`.
+pub fn spawn_analysis(uri: String, bus: Bus) {
+    thread::spawn(move || {
+        let structure = Structure::builder(LOUDNESS_MEASURED).field("uri", uri.as_str());
+        let structure = match measure_track_gain(&uri) {
+            Ok(gain) => structure.field("gain", gain),
+            Err(err) => structure.field("error", err.to_string()),
+        };
+        bus.post(message::Application::new(structure.build())).ok();
+    });
+}
+
+fn measure_track_gain(uri: &str) -> Result<f64, MediaPlayerErrors> {
+    let pipeline = Pipeline::with_name("loudness");
+    let source = ElementFactory::make("uridecodebin")
+        .property("uri", uri)
+        .build()
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+    let convert = ElementFactory::make("audioconvert")
+        .build()
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+    let resample = ElementFactory::make("audioresample")
+        .build()
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+    let analysis = ElementFactory::make("rganalysis")
+        .build()
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+    let fakesink = ElementFactory::make("fakesink")
+        .property("sync", false)
+        .build()
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+
+    pipeline
+        .add_many([&source, &convert, &resample, &analysis, &fakesink])
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+    Element::link_many([&convert, &resample, &analysis, &fakesink])
+        .map_err(MediaPlayerErrors::ErrorLoudnessAnalysis)?;
+
+    // Only the first audio stream is measured, the other streams are discarded
+    let pipeline_weak = pipeline.downgrade();
+    let convert_weak = convert.downgrade();
+    source.connect_pad_added(move |_, pad| {
+        let (Some(pipeline), Some(convert)) = (pipeline_weak.upgrade(), convert_weak.upgrade())
+        else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        let is_audio = caps
+            .structure(0)
+            .is_some_and(|structure| structure.name().starts_with("audio/"));
+        match convert.static_pad("sink") {
+            Some(sink) if is_audio && !sink.is_linked() => {
+                pad.link(&sink).ok();
+            }
+            _ => transcoder::discard_pad(&pipeline, pad),
+        }
+    });
+
+    let bus = pipeline.bus().unwrap();
+    pipeline
+        .set_state(State::Playing)
+        .map_err(MediaPlayerErrors::ErrorStartingLoudnessAnalysis)?;
+
+    let mut result = Err(MediaPlayerErrors::ErrorNoLoudness);
+    for msg in bus.iter_timed(ClockTime::NONE) {
+        match msg.view() {
+            MessageView::Tag(tag) => {
+                if let Some(value) = tag.tags().get::<tags::TrackGain>() {
+                    result = Ok(value.get());
+                }
+            }
+            MessageView::Eos(_) => break,
+            MessageView::Error(err) => {
+                result = Err(MediaPlayerErrors::ErrorMeasuringLoudness(err.error()));
+                break;
+            }
+            _ => (),
+        }
+    }
+
+    pipeline.set_state(State::Null).ok();
+    result
+}
@@ -1,24 +1,113 @@
 use {
     aug_media_player::{
+        config::Args,
+        decoders, devices,
         media_player::{MediaPlayer, handle_message},
+        playlist::Playlist,
+        transcoder::{TranscodeEvent, TranscodeOptions, Transcoder},
         ui::build_ui,
-        config::Args,
     },
     clap::Parser,
     gtk::{Application, glib, prelude::*},
-    std::{cell::RefCell, rc::Rc},
+    std::{
+        cell::{Cell, RefCell},
+        io::{self, Write},
+        path::Path,
+        rc::Rc,
+    },
 };
 
 const APP_ID: &str = "org.AugMediaPlayer";
 
 fn main() -> glib::ExitCode {
     let args = Args::parse();
-    let uri = args.formatted_uri();
 
     gstreamer::init().expect("Unable to initialize GStreamer");
 
+    if args.list_devices {
+        devices::print_devices();
+        return glib::ExitCode::SUCCESS;
+    }
+
+    let uri = match args.formatted_uri() {
+        Ok(uri) => uri,
+        Err(err) => {
+            println!("{}", err);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    if let (Some(uri), Some((output, options))) = (&uri, args.transcoding()) {
+        decoders::apply_hardware_decoding(args.hw_decoding);
+        return run_transcoding(uri, output, &options);
+    }
+
     let app = Application::builder().application_id(APP_ID).build();
-    let media_player = Rc::new(RefCell::new(MediaPlayer::build(uri)));
+    // Playlists and directories are played as a queue, starting with their first media
+    let playlist = args.directory_queue().or_else(|| {
+        uri.as_deref()
+            .map(|uri| Playlist::load(uri).map_err(|err| err.to_string()))
+            .and_then(Result::transpose)
+    });
+    let playlist = match playlist {
+        Some(Ok(playlist)) => Some(playlist),
+        Some(Err(err)) => {
+            println!("{}", err);
+            return glib::ExitCode::FAILURE;
+        }
+        None => None,
+    };
+
+    // Without URI, the window shows the start page until a media is opened
+    let media_uri = uri.filter(|_| playlist.is_none());
+    let has_media = media_uri.is_some();
+    let media_player = Rc::new(RefCell::new(MediaPlayer::build(
+        media_uri.unwrap_or_default(),
+    )));
+    if let Err(err) = media_player
+        .borrow_mut()
+        .set_normalization(args.normalization, args.target_lufs)
+    {
+        println!("{}", err);
+    }
+    media_player
+        .borrow_mut()
+        .set_volume_boost(args.volume_boost)
+        .ok();
+    if let Some(origin) = args.origin() {
+        media_player.borrow_mut().set_origin(origin);
+    }
+    media_player.borrow_mut().set_quality(args.quality);
+    media_player
+        .borrow_mut()
+        .set_hardware_decoding(args.hw_decoding);
+    media_player
+        .borrow_mut()
+        .set_deinterlace(args.deinterlace, args.deinterlace_method);
+    if let Err(err) = media_player
+        .borrow_mut()
+        .set_visualization(args.visualization)
+    {
+        println!("{}", err);
+    }
+    if let Some(name) = &args.audio_device {
+        match devices::find_audio_sink(name) {
+            Some(device) => {
+                if let Err(err) = media_player.borrow_mut().set_audio_device(Some(device)) {
+                    println!("{}", err);
+                }
+            }
+            None => println!("No audio output named {}, see --list-devices", name),
+        }
+    }
+    media_player.borrow().set_buffering(
+        args.buffer_size,
+        args.buffer_duration
+            .map(gstreamer::ClockTime::from_mseconds),
+    );
+    media_player
+        .borrow_mut()
+        .set_recording_settings(args.recording_settings());
 
     let media_player_clone = media_player.clone();
 
@@ -35,8 +124,104 @@ fn main() -> glib::ExitCode {
 
     app.connect_activate(move |application| {
         build_ui(application, media_player.clone());
-        media_player.borrow().play_player().ok();
+        if let Some(playlist) = &playlist {
+            if let Err(err) = media_player.borrow_mut().set_queue(playlist.clone(), 0) {
+                println!("{}", err);
+            }
+        } else if has_media {
+            media_player.borrow().play_player().ok();
+        }
+        if args.record
+            && let Err(err) = media_player.borrow_mut().start_recording()
+        {
+            println!("{}", err);
+        }
     });
 
     app.run_with_args::<String>(&[])
 }
+
+/// Width of the progress bar printed by [`run_transcoding`], in characters
+const PROGRESS_BAR_WIDTH: usize = 40;
+
+fn print_progress(progress: f64) {
+    let filled = (progress * PROGRESS_BAR_WIDTH as f64).round() as usize;
+    print!(
+        "\r[{}{}] {:3.0}%",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        progress * 100.0
+    );
+    io::stdout().flush().ok();
+}
+
+/// Run the `export` or `convert` command without the window, with a progress bar on the
+/// terminal. Ctrl+C cancels the transcoding and removes the partial file.
+fn run_transcoding(uri: &str, output: &Path, options: &TranscodeOptions) -> glib::ExitCode {
+    let main_loop = glib::MainLoop::new(None, false);
+    let succeeded = Rc::new(Cell::new(false));
+
+    let transcoder = Transcoder::start(
+        uri,
+        output,
+        options,
+        glib::clone!(
+            #[strong]
+            main_loop,
+            #[strong]
+            succeeded,
+            move |event| match event {
+                TranscodeEvent::Progress(progress) => print_progress(progress),
+                TranscodeEvent::Finished => {
+                    print_progress(1.0);
+                    println!();
+                    succeeded.set(true);
+                    main_loop.quit();
+                }
+                TranscodeEvent::Failed(err) => {
+                    println!("\nTranscoding failed: {}", err);
+                    main_loop.quit();
+                }
+            }
+        ),
+    );
+    let transcoder = match transcoder {
+        Ok(transcoder) => Rc::new(RefCell::new(Some(transcoder))),
+        Err(err) => {
+            println!("{}", err);
+            return glib::ExitCode::FAILURE;
+        }
+    };
+
+    #[cfg(unix)]
+    {
+        const SIGINT: i32 = 2;
+        glib::unix_signal_add_local(
+            SIGINT,
+            glib::clone!(
+                #[strong]
+                main_loop,
+                #[strong]
+                transcoder,
+                move || {
+                    if let Some(transcoder) = transcoder.borrow_mut().take() {
+                        transcoder.cancel();
+                        println!("\nTranscoding cancelled");
+                    }
+                    main_loop.quit();
+                    glib::ControlFlow::Break
+                }
+            ),
+        );
+    }
+
+    main_loop.run();
+    drop(transcoder);
+
+    if succeeded.get() {
+        println!("Saved to {}", output.display());
+        glib::ExitCode::SUCCESS
+    } else {
+        glib::ExitCode::FAILURE
+    }
+}
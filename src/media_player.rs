@@ -1,10 +1,32 @@
 use {
+    crate::{
+        adaptive::{self, MANIFEST_RECEIVED, QualityLock, Variant},
+        audio_filter::AudioFilter,
+        bookmarks::{self, Bookmark, Bookmarks, WatchLater},
+        config::{NormalizationMode, OriginType, RtspTransport, SrtMode},
+        decoders::{self, DECODER_SELECTED, HardwareDecoding, VideoDecoder},
+        devices::{self, AUDIO_DEVICES_CHANGED},
+        history::{History, RecentMedia},
+        loudness::{self, LOUDNESS_MEASURED},
+        media_settings::MediaSettings,
+        playlist::{Playlist, PlaylistEntry},
+        recorder::{RECORDING_ENDED, Recorder, RecordingSettings, media_title},
+        video_filter::{
+            Deinterlace, DeinterlaceMethod, ORIENTATION_CHANGED, VideoBalance, VideoFilter,
+        },
+        video_transform::VideoTransform,
+        visualization::{self, Visualization},
+    },
     gstgtk4::PaintableSink,
     gstreamer::{prelude::*, *},
-    gtk::glib,
+    gstreamer_audio::{StreamVolume, StreamVolumeFormat},
+    gtk::{gdk, glib},
     std::{
-        cell::{RefCell, RefMut},
+        cell::{Cell, RefCell, RefMut},
+        path::{Path, PathBuf},
         rc::Rc,
+        sync::{Arc, Mutex},
+        time::Duration,
     },
     thiserror::Error,
 };
@@ -22,41 +44,319 @@ pub struct MediaPlayer {
     gtk_video: PaintableSink,
 
     user_is_seeking: bool,
-    /// Current volume (0.0 to 1.0)
-    volume: f64,
-    /// Is the player muted?
-    muted: bool,
-    /// Volume before muting (to restore when unmuting)
-    volume_before_mute: f64,
+    /// Highest volume allowed, 1.0 or 2.0 when the boost is enabled
+    max_volume: f64,
+
+    /// URI of the media being played
+    uri: String,
+    /// Loudness normalization mode
+    normalization: NormalizationMode,
+    /// Target loudness of the normalization, in LUFS
+    target_lufs: f64,
+    /// Filter bin applied to the audio before it reaches the sink
+    audio_filter: AudioFilter,
+    /// Filter bin applied to the video before it reaches the sink
+    video_filter: VideoFilter,
+    /// Picture adjustments of the media
+    video_balance: VideoBalance,
+    /// Delay of the audio relative to the video, in milliseconds
+    av_offset: i64,
+    /// When the video is deinterlaced
+    deinterlace: Deinterlace,
+    /// Algorithm of the deinterlacing
+    deinterlace_method: DeinterlaceMethod,
+    /// Settings remembered for each media
+    media_settings: MediaSettings,
+    /// Are changed media settings waiting to be saved?
+    media_settings_changed: bool,
+    /// What is shown in place of the picture of audio-only media
+    visualization: Visualization,
+    /// Cover art embedded in the media
+    cover_art: Option<gdk::Texture>,
+    /// Does the media have audio and no video?
+    audio_only: bool,
+    /// Does the media carry its own ReplayGain tags?
+    has_replaygain_tags: bool,
+    /// Has a loudness analysis pass been started for this media?
+    loudness_analysis_started: bool,
+    /// Start of the A-B loop (point A)
+    loop_start: Option<ClockTime>,
+    /// End of the A-B loop (point B)
+    loop_end: Option<ClockTime>,
+    /// Start of the clip to export
+    clip_in: Option<ClockTime>,
+    /// End of the clip to export
+    clip_out: Option<ClockTime>,
+    /// Chapters of the media, sorted by start time
+    chapters: Vec<Chapter>,
+    /// State requested by the user, restored once the buffer is full
+    target_state: Cell<State>,
+    /// Buffer fill level of network streams, from 0 to 100
+    buffering_percent: i32,
+    /// Is the media a live source? Live sources do not buffer
+    is_live: bool,
+    /// Origin of the media, used to configure the source created by playbin
+    origin: Arc<Mutex<OriginType>>,
+    /// Was the media opened from a live origin (RTSP, SRT, UDP or a device)?
+    live_origin: bool,
+    /// Latency of live sources, reported by the pipeline
+    live_latency: Option<ClockTime>,
+    /// Reconnections attempted since the stream last played
+    reconnect_attempts: u32,
+    /// Is a reconnection waiting for its backoff delay?
+    reconnect_pending: bool,
+    /// Did the media play since it was opened? Streams failing to open are not reconnected
+    has_played: bool,
+    /// Position to seek to once the stream is reconnected
+    resume_position: Option<ClockTime>,
+    /// Demuxer of the HLS or DASH manifest, for adaptive streams
+    demuxer: Option<Element>,
+    /// Bitrate variants listed in the manifest, sorted by bandwidth
+    variants: Vec<Variant>,
+    /// Quality selection of adaptive streams
+    quality: QualityLock,
+    /// Last measured download bandwidth, in bits per second
+    bandwidth: Option<u64>,
+    /// Use of the hardware video decoders
+    hardware_decoding: HardwareDecoding,
+    /// Video decoder plugged for the media
+    video_decoder: Option<VideoDecoder>,
+    /// Element of the video decoder, to recognize its errors
+    decoder_element: Option<Element>,
+    /// Recording of the media in progress
+    recorder: Option<Recorder>,
+    /// Was a recording asked for before the streams were ready?
+    recording_pending: bool,
+    /// Format, directory and file name of the recordings
+    recording_settings: RecordingSettings,
+    /// Audio output playing the media, the default one when `None`
+    audio_device: Option<Device>,
+    /// Media opened recently
+    history: History,
+    /// Was the media added to the history since it was opened?
+    in_history: bool,
+    /// Title tag of the media, named in the history
+    title: Option<String>,
+    /// Bookmarks and watch-later marks of the media
+    bookmarks: Bookmarks,
+    /// Media played one after the other, from a playlist
+    queue: Playlist,
+    /// Entry of the queue being played
+    queue_index: Option<usize>,
+    /// Watch of the plugged and unplugged audio outputs
+    audio_monitor: Option<DeviceMonitor>,
+    /// Callbacks notified of the player events
+    listeners: Rc<RefCell<Vec<Box<dyn Fn(&PlayerEvent)>>>>,
+}
+
+/// Name of the application message posted when playbin's volume or mute state changes
+const VOLUME_CHANGED: &str = "aug-volume-changed";
+/// Name of the application message posted when a reconnection backoff delay is over
+const RECONNECT: &str = "aug-reconnect";
+/// Name of the application message posted to save the changed media settings
+const SAVE_MEDIA_SETTINGS: &str = "aug-save-media-settings";
+/// Delay before saving the media settings, so dragging a slider writes them once
+const MEDIA_SETTINGS_SAVE_DELAY: Duration = Duration::from_millis(500);
+/// Reconnections attempted before giving up on a network stream
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+/// Time given to a recording to finish its file once stopped
+const RECORDING_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Media stopped closer than this to their end play from the start when opened again
+const RESTART_MARGIN: ClockTime = ClockTime::from_seconds(5);
+/// Key of the audio/video offset in the media settings
+const AV_OFFSET_KEY: &str = "av-offset";
+
+/// Events emitted by the player, see [`MediaPlayer::connect_event`]
+#[derive(Debug, Clone)]
+pub enum PlayerEvent {
+    /// The volume or the mute state changed, either from the player or externally
+    VolumeChanged { volume: f64, muted: bool },
+    /// The duration of the media is known
+    DurationChanged,
+    /// The chapter list was read from the container
+    ChaptersChanged,
+    /// The buffer of a network stream is filling, playback resumes at 100%
+    Buffering(i32),
+    /// The network stream failed, a reconnection is scheduled
+    Reconnecting { attempt: u32 },
+    /// The bitrate variants of an adaptive stream were read from its manifest
+    VariantsChanged,
+    /// A new media was opened, its per-media state is reset
+    MediaChanged,
+    /// The media started playing, and is known to be seekable or live
+    SeekingChanged { seekable: bool, live: bool },
+    /// A video decoder was plugged, see [`MediaPlayer::video_decoder`]
+    DecoderChanged,
+    /// The picture adjustments changed, or were loaded for a new media
+    VideoBalanceChanged,
+    /// The rotation, flips, crop, zoom or aspect ratio of the picture changed
+    VideoTransformChanged,
+    /// The deinterlacing mode or method changed
+    DeinterlaceChanged,
+    /// The audio/video offset changed, or was loaded for a new media
+    AvOffsetChanged,
+    /// The visualization, the cover art or the presence of a video stream changed
+    VisualizationChanged,
+    /// Media were added to or removed from the history
+    HistoryChanged,
+    /// A playlist was opened, or another entry of it plays
+    QueueChanged,
+    /// Bookmarks of the media were added, edited or removed, or a new media was opened
+    BookmarksChanged,
+    /// The media was put aside to watch later, or taken back
+    WatchLaterChanged,
+    /// A recording started or ended
+    RecordingChanged { recording: bool },
+    /// An audio output was plugged or unplugged, or another one was selected
+    AudioDevicesChanged,
+    /// A short message to show over the video, see [`MediaPlayer::show_osd`]
+    Osd(String),
+}
+
+/// A chapter of the media, read from the container table of contents
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub start: ClockTime,
+    pub end: Option<ClockTime>,
 }
 
 impl MediaPlayer {
     pub fn build(uri: impl AsRef<str>) -> Self {
         let playbin = ElementFactory::make("playbin")
             .name("playbin")
-            // Set the URI to play
-            .property("uri", uri.as_ref())
+            // Set the URI to play, the start page is shown until a media is opened
+            .property_if("uri", uri.as_ref(), !uri.as_ref().is_empty())
             .build()
             .expect("Failed to create playbin element");
 
         let videosink = PaintableSink::new(Some("gtk4paintablesink"));
 
         playbin.set_property("video-sink", &videosink);
-        
-        // Set default volume to 50%
-        playbin.set_property("volume", 0.5f64);
 
-        Self {
+        let audio_filter = AudioFilter::build(NormalizationMode::Off, -18.0)
+            .expect("Failed to create audio filter");
+        playbin.set_property("audio-filter", &audio_filter.bin);
+
+        let video_filter = VideoFilter::build().expect("Failed to create video filter");
+        playbin.set_property("video-filter", &video_filter.bin);
+        // The video filter deinterlaces following `--deinterlace`, so playsink must not
+        // deinterlace the video a second time
+        set_play_flag(&playbin, "deinterlace", false);
+        let media_settings = MediaSettings::load();
+        let video_balance = VideoBalance::load(&media_settings, uri.as_ref());
+        video_filter.set_balance(&video_balance);
+        let av_offset = media_settings
+            .int64(uri.as_ref(), AV_OFFSET_KEY)
+            .unwrap_or(0);
+        playbin.set_property("av-offset", av_offset * 1_000_000);
+
+        let audio_monitor = devices::watch_audio_sinks(&playbin.bus().unwrap());
+
+        let mut media_player = Self {
             playbin,
             playing: false,
             seek_enabled: false,
             duration: ClockTime::NONE,
             gtk_video: videosink,
             user_is_seeking: false,
-            volume: 0.5,
-            muted: false,
-            volume_before_mute: 0.5,
+            max_volume: 1.0,
+            uri: uri.as_ref().to_string(),
+            normalization: NormalizationMode::Off,
+            target_lufs: -18.0,
+            audio_filter,
+            video_filter,
+            video_balance,
+            av_offset,
+            deinterlace: Deinterlace::Auto,
+            deinterlace_method: DeinterlaceMethod::Greedyh,
+            media_settings,
+            media_settings_changed: false,
+            visualization: Visualization::Off,
+            cover_art: None,
+            audio_only: false,
+            has_replaygain_tags: false,
+            loudness_analysis_started: false,
+            loop_start: ClockTime::NONE,
+            loop_end: ClockTime::NONE,
+            clip_in: ClockTime::NONE,
+            clip_out: ClockTime::NONE,
+            chapters: Vec::new(),
+            target_state: Cell::new(State::Null),
+            buffering_percent: 100,
+            is_live: false,
+            origin: Arc::new(Mutex::new(OriginType::File)),
+            live_origin: false,
+            live_latency: ClockTime::NONE,
+            reconnect_attempts: 0,
+            reconnect_pending: false,
+            has_played: false,
+            resume_position: ClockTime::NONE,
+            demuxer: None,
+            variants: Vec::new(),
+            quality: QualityLock::Auto,
+            bandwidth: None,
+            hardware_decoding: HardwareDecoding::Auto,
+            video_decoder: None,
+            decoder_element: None,
+            recorder: None,
+            recording_pending: false,
+            recording_settings: RecordingSettings::default(),
+            audio_device: None,
+            audio_monitor,
+            history: History::load(),
+            in_history: false,
+            title: None,
+            bookmarks: Bookmarks::load(),
+            queue: Playlist::default(),
+            queue_index: None,
+            listeners: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // Set default volume to 50%
+        media_player.apply_volume(0.5);
+
+        // Playbin is the source of truth for the volume and mute state, which the system
+        // mixer can also change. The notifications may come from a streaming thread,
+        // so they are forwarded through the bus to `handle_message`.
+        for property in ["volume", "mute"] {
+            media_player
+                .playbin
+                .connect_notify(Some(property), |playbin, _| {
+                    let structure = Structure::new_empty(VOLUME_CHANGED);
+                    playbin
+                        .post_message(
+                            message::Application::builder(structure)
+                                .src(playbin)
+                                .build(),
+                        )
+                        .ok();
+                });
         }
+
+        // The source is created by playbin once the URI is opened, it is configured for
+        // the current origin from the thread doing the state change
+        let origin = media_player.origin.clone();
+        media_player
+            .playbin
+            .connect("source-setup", false, move |values| {
+                if let Ok(source) = values[1].get::<Element>() {
+                    configure_source(&source, &origin.lock().unwrap());
+                }
+                None
+            });
+
+        // Adaptive demuxers and decoders are only created once the media type is known
+        if let Some(bin) = media_player.playbin.dynamic_cast_ref::<Bin>() {
+            bin.connect_deep_element_added(|_, _, element| {
+                if adaptive::is_adaptive_demuxer(element) {
+                    adaptive::watch_manifest(element);
+                }
+                decoders::watch_decoder(element);
+            });
+        }
+
+        media_player
     }
     // Getters
 
@@ -84,10 +384,249 @@ impl MediaPlayer {
         self.playbin.bus().unwrap()
     }
 
+    /// URI of the media being played
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
     pub fn playing(&self) -> bool {
         self.playing
     }
 
+    pub fn buffering_percent(&self) -> i32 {
+        self.buffering_percent
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.is_live
+    }
+
+    /// How far behind the live edge the playback runs
+    pub fn live_latency(&self) -> Option<ClockTime> {
+        self.live_latency
+    }
+
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    pub fn quality(&self) -> QualityLock {
+        self.quality
+    }
+
+    /// Last measured download bandwidth of adaptive streams, in bits per second
+    pub fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth
+    }
+
+    pub fn video_balance(&self) -> VideoBalance {
+        self.video_balance
+    }
+
+    /// Adjust the picture, the values are remembered for the media
+    pub fn set_video_balance(&mut self, balance: VideoBalance) -> Result<(), MediaPlayerErrors> {
+        if balance == self.video_balance {
+            return Ok(());
+        }
+        self.video_balance = balance;
+        self.video_filter.set_balance(&balance);
+        self.emit(PlayerEvent::VideoBalanceChanged);
+
+        balance.store(&self.media_settings, &self.uri);
+        self.media_settings_changed();
+        Ok(())
+    }
+
+    /// Delay of the audio relative to the video, in milliseconds
+    pub fn av_offset(&self) -> i64 {
+        self.av_offset
+    }
+
+    /// Shift the audio against the video to fix the lip-sync. Positive values delay the
+    /// audio, negative values delay the video. The offset is remembered for the media.
+    pub fn set_av_offset(&mut self, milliseconds: i64) -> Result<(), MediaPlayerErrors> {
+        if milliseconds == self.av_offset {
+            return Ok(());
+        }
+        self.apply_av_offset(milliseconds);
+        self.show_osd(format!("A/V offset {:+} ms", milliseconds));
+
+        if milliseconds == 0 {
+            self.media_settings.remove(&self.uri, AV_OFFSET_KEY);
+        } else {
+            self.media_settings
+                .set_int64(&self.uri, AV_OFFSET_KEY, milliseconds);
+        }
+        self.media_settings_changed();
+        Ok(())
+    }
+
+    /// Save the media settings after [`MEDIA_SETTINGS_SAVE_DELAY`], once for all the
+    /// changes made in the meantime
+    fn media_settings_changed(&mut self) {
+        if self.media_settings_changed {
+            return;
+        }
+        self.media_settings_changed = true;
+        let bus = self.get_bus();
+        glib::timeout_add_local_once(MEDIA_SETTINGS_SAVE_DELAY, move || {
+            bus.post(message::Application::new(Structure::new_empty(
+                SAVE_MEDIA_SETTINGS,
+            )))
+            .ok();
+        });
+    }
+
+    /// Write the changed media settings, if any
+    fn save_media_settings(&mut self) {
+        if !std::mem::take(&mut self.media_settings_changed) {
+            return;
+        }
+        if let Err(err) = self.media_settings.save() {
+            println!("{}", err);
+        }
+    }
+
+    fn apply_av_offset(&mut self, milliseconds: i64) {
+        self.av_offset = milliseconds;
+        self.playbin
+            .set_property("av-offset", milliseconds * 1_000_000);
+        self.emit(PlayerEvent::AvOffsetChanged);
+    }
+
+    pub fn deinterlace(&self) -> Deinterlace {
+        self.deinterlace
+    }
+
+    pub fn deinterlace_method(&self) -> DeinterlaceMethod {
+        self.deinterlace_method
+    }
+
+    /// Choose when and how the video is deinterlaced. `Deinterlace::Auto` only
+    /// deinterlaces media whose caps say they are interlaced.
+    pub fn set_deinterlace(&mut self, mode: Deinterlace, method: DeinterlaceMethod) {
+        if (mode, method) == (self.deinterlace, self.deinterlace_method) {
+            return;
+        }
+        self.deinterlace = mode;
+        self.deinterlace_method = method;
+        self.video_filter.set_deinterlace(mode, method);
+        self.emit(PlayerEvent::DeinterlaceChanged);
+    }
+
+    pub fn audio_device(&self) -> Option<&Device> {
+        self.audio_device.as_ref()
+    }
+
+    /// Audio outputs currently plugged
+    pub fn audio_devices(&self) -> Vec<Device> {
+        match &self.audio_monitor {
+            Some(monitor) => monitor.devices().into_iter().collect(),
+            None => devices::list_audio_sinks(),
+        }
+    }
+
+    /// Play the audio on `device`, or on the default output when `None`. playbin only
+    /// accepts a new audio sink when stopped, so the media is played again from the
+    /// same position.
+    pub fn set_audio_device(&mut self, device: Option<Device>) -> Result<(), MediaPlayerErrors> {
+        let sink = device
+            .as_ref()
+            .map(|device| device.create_element(None))
+            .transpose()
+            .map_err(MediaPlayerErrors::ErrorAudioDevice)?;
+
+        let running = self.playbin.current_state() > State::Ready;
+        if running {
+            if self.resume_position.is_none() && self.seek_enabled {
+                self.resume_position = self.get_position().ok();
+            }
+            self.playbin
+                .set_state(State::Ready)
+                .map_err(MediaPlayerErrors::Errorstopping)?;
+        }
+        self.playbin.set_property("audio-sink", sink.as_ref());
+        if let Some(device) = &device {
+            self.show_osd(format!("Audio: {}", device.display_name()));
+        }
+        self.audio_device = device;
+        self.emit(PlayerEvent::AudioDevicesChanged);
+        if running {
+            self.playbin
+                .set_state(self.target_state.get())
+                .map_err(MediaPlayerErrors::ErrorPlaying)?;
+        }
+        Ok(())
+    }
+
+    pub fn visualization(&self) -> Visualization {
+        self.visualization
+    }
+
+    /// Choose what is shown in place of the picture of audio-only media
+    pub fn set_visualization(
+        &mut self,
+        visualization: Visualization,
+    ) -> Result<(), MediaPlayerErrors> {
+        visualization::apply_visualization(&self.playbin, visualization)
+            .map_err(MediaPlayerErrors::ErrorVisualization)?;
+        self.visualization = visualization;
+        self.emit(PlayerEvent::VisualizationChanged);
+        Ok(())
+    }
+
+    pub fn cover_art(&self) -> Option<&gdk::Texture> {
+        self.cover_art.as_ref()
+    }
+
+    /// Does the media have audio and no video? Known once it plays.
+    pub fn is_audio_only(&self) -> bool {
+        self.audio_only
+    }
+
+    /// Rotation, flips, crop, zoom and aspect ratio of the picture
+    pub fn video_transform(&self) -> VideoTransform {
+        self.video_filter.transform()
+    }
+
+    /// Change the geometry of the picture, e.g. `|transform| transform.rotate(true)`
+    pub fn update_video_transform(&self, update: impl FnOnce(&mut VideoTransform)) {
+        let mut transform = self.video_filter.transform();
+        update(&mut transform);
+        if transform == self.video_filter.transform() {
+            return;
+        }
+        self.video_filter.set_transform(&transform);
+        self.emit(PlayerEvent::VideoTransformChanged);
+    }
+
+    pub fn hardware_decoding(&self) -> HardwareDecoding {
+        self.hardware_decoding
+    }
+
+    pub fn video_decoder(&self) -> Option<&VideoDecoder> {
+        self.video_decoder.as_ref()
+    }
+
+    /// Variant being played, matched by the height of the decoded video
+    pub fn current_variant(&self) -> Option<&Variant> {
+        let index = self.playbin.property::<i32>("current-video");
+        let pad = self
+            .playbin
+            .emit_by_name::<Option<Pad>>("get-video-pad", &[&index])?;
+        let caps = pad.current_caps()?;
+        let height = caps.structure(0)?.get::<i32>("height").ok()?;
+        self.variants
+            .iter()
+            .rev()
+            .find(|variant| variant.height == u32::try_from(height).ok())
+    }
+
+    /// Is the media streamed over HTTP?
+    pub fn is_network_stream(&self) -> bool {
+        self.uri.starts_with("http://") || self.uri.starts_with("https://")
+    }
+
     pub fn get_position(&self) -> Result<ClockTime, MediaPlayerErrors> {
         self.playbin.query_position::<ClockTime>().map_or_else(
             || Err(MediaPlayerErrors::ErrorGettingPosition),
@@ -95,12 +634,37 @@ impl MediaPlayer {
         )
     }
 
+    pub fn normalization(&self) -> NormalizationMode {
+        self.normalization
+    }
+
+    /// Gain applied by the loudness normalization, in dB
+    pub fn applied_gain(&self) -> Option<f64> {
+        self.audio_filter
+            .rgvolume
+            .as_ref()
+            .map(|rgvolume| rgvolume.property::<f64>("result-gain"))
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    /// Index of the chapter being played
+    pub fn current_chapter(&self) -> Option<usize> {
+        let position = self.get_position().ok()?;
+        self.chapters
+            .iter()
+            .rposition(|chapter| chapter.start <= position)
+    }
+
     // Setters
     pub fn set_user_is_seeking(&mut self, user_is_seeking: bool) {
         self.user_is_seeking = user_is_seeking;
     }
 
     pub fn pause_player(&self) -> Result<(), MediaPlayerErrors> {
+        self.target_state.set(State::Paused);
         self.playbin
             .set_state(State::Paused)
             .map_err(MediaPlayerErrors::ErrorPausing)
@@ -108,6 +672,11 @@ impl MediaPlayer {
     }
 
     pub fn play_player(&self) -> Result<(), MediaPlayerErrors> {
+        self.target_state.set(State::Playing);
+        // The player starts by itself once the buffer is full
+        if self.buffering_percent < 100 {
+            return Ok(());
+        }
         self.playbin
             .set_state(State::Playing)
             .map_err(MediaPlayerErrors::ErrorPlaying)
@@ -115,6 +684,7 @@ impl MediaPlayer {
     }
 
     pub fn stop_player(&self) -> Result<(), MediaPlayerErrors> {
+        self.target_state.set(State::Ready);
         self.playbin
             .set_state(State::Ready)
             .map_err(MediaPlayerErrors::Errorstopping)
@@ -126,20 +696,17 @@ impl MediaPlayer {
             return Ok(());
         }
         let position = self.get_position()?;
-        self.playbin
-            .seek_simple(
-                SeekFlags::FLUSH | SeekFlags::KEY_UNIT,
-                position + (10 * ClockTime::SECOND),
-            )
-            .map_err(|err| MediaPlayerErrors::ErrorSeekingForward(err))
+        self.seek_to(position + (10 * ClockTime::SECOND))
+            .map_err(|err| MediaPlayerErrors::ErrorSeekingForward(err))?;
+        self.show_osd("+10s");
+        Ok(())
     }
 
     pub fn seek_position(&self, position: ClockTime) -> Result<(), MediaPlayerErrors> {
         if !self.seek_enabled {
             return Ok(());
         }
-        self.playbin
-            .seek_simple(SeekFlags::FLUSH | SeekFlags::KEY_UNIT, position)
+        self.seek_to(position)
             .map_err(|err| MediaPlayerErrors::ErrorSeeking(err))
     }
 
@@ -154,52 +721,897 @@ impl MediaPlayer {
             ClockTime::ZERO
         };
 
-        self.playbin
-            .seek_simple(SeekFlags::FLUSH | SeekFlags::KEY_UNIT, new_position)
-            .map_err(|err| MediaPlayerErrors::ErrorSeekingBackward(err))
+        self.seek_to(new_position)
+            .map_err(|err| MediaPlayerErrors::ErrorSeekingBackward(err))?;
+        self.show_osd("-10s");
+        Ok(())
     }
 
-    /// Set the volume (0.0 to 1.0)
-    pub fn set_volume(&mut self, volume: f64) -> Result<(), MediaPlayerErrors> {
-        let clamped_volume = volume.clamp(0.0, 1.0);
-        self.playbin.set_property("volume", clamped_volume);
-        self.volume = clamped_volume;
-        if !self.muted {
-            self.volume_before_mute = clamped_volume;
+    /// Flushing seek, keeping the A-B loop running when there is one
+    fn seek_to(&self, position: ClockTime) -> Result<(), glib::BoolError> {
+        match self.active_loop() {
+            Some((start, end)) => self.playbin.seek(
+                1.0,
+                SeekFlags::FLUSH | SeekFlags::SEGMENT | SeekFlags::ACCURATE,
+                SeekType::Set,
+                position.clamp(start, end),
+                SeekType::Set,
+                end,
+            ),
+            None => self
+                .playbin
+                .seek_simple(SeekFlags::FLUSH | SeekFlags::KEY_UNIT, position),
+        }
+    }
+
+    /// The A-B loop, when both points are set
+    pub fn active_loop(&self) -> Option<(ClockTime, ClockTime)> {
+        self.loop_start.zip(self.loop_end)
+    }
+
+    pub fn loop_start(&self) -> Option<ClockTime> {
+        self.loop_start
+    }
+
+    pub fn loop_end(&self) -> Option<ClockTime> {
+        self.loop_end
+    }
+
+    /// Loop seamlessly between `a` and `b`, using segment seeks
+    pub fn set_loop(&mut self, a: ClockTime, b: ClockTime) -> Result<(), MediaPlayerErrors> {
+        if !self.seek_enabled {
+            return Ok(());
+        }
+        if a >= b {
+            return Err(MediaPlayerErrors::ErrorInvalidLoop);
+        }
+        self.loop_start = Some(a);
+        self.loop_end = Some(b);
+        self.seek_to(a).map_err(MediaPlayerErrors::ErrorSeeking)
+    }
+
+    /// Set point A of the loop at the current position
+    pub fn set_loop_start_here(&mut self) -> Result<(), MediaPlayerErrors> {
+        let position = self.get_position()?;
+        self.show_osd(format!("Loop A {}", short_time(position)));
+        match self.loop_end {
+            Some(end) if position < end => self.set_loop(position, end),
+            _ => {
+                self.loop_start = Some(position);
+                self.loop_end = ClockTime::NONE;
+                Ok(())
+            }
+        }
+    }
+
+    /// Set point B of the loop at the current position, starting the loop if A is set
+    pub fn set_loop_end_here(&mut self) -> Result<(), MediaPlayerErrors> {
+        let position = self.get_position()?;
+        self.show_osd(format!("Loop B {}", short_time(position)));
+        match self.loop_start {
+            Some(start) => self.set_loop(start, position),
+            None => {
+                self.loop_end = Some(position);
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove the loop points and keep playing from the current position
+    pub fn clear_loop(&mut self) -> Result<(), MediaPlayerErrors> {
+        let was_looping = self.active_loop().is_some();
+        if self.loop_start.is_some() || self.loop_end.is_some() {
+            self.show_osd("Loop cleared");
+        }
+        self.loop_start = ClockTime::NONE;
+        self.loop_end = ClockTime::NONE;
+        if !was_looping || !self.seek_enabled {
+            return Ok(());
+        }
+        // A plain flushing seek drops the segment stop position
+        let position = self.get_position()?;
+        self.seek_to(position)
+            .map_err(MediaPlayerErrors::ErrorSeeking)
+    }
+
+    pub fn clip_in(&self) -> Option<ClockTime> {
+        self.clip_in
+    }
+
+    pub fn clip_out(&self) -> Option<ClockTime> {
+        self.clip_out
+    }
+
+    /// Set the start of the clip to export at the current position
+    pub fn set_clip_in_here(&mut self) -> Result<(), MediaPlayerErrors> {
+        let position = self.get_position()?;
+        self.show_osd(format!("Clip in {}", short_time(position)));
+        self.clip_in = Some(position);
+        if self.clip_out.is_some_and(|out| out <= position) {
+            self.clip_out = ClockTime::NONE;
+        }
+        Ok(())
+    }
+
+    /// Set the end of the clip to export at the current position
+    pub fn set_clip_out_here(&mut self) -> Result<(), MediaPlayerErrors> {
+        let position = self.get_position()?;
+        self.show_osd(format!("Clip out {}", short_time(position)));
+        self.clip_out = Some(position);
+        if self.clip_in.is_some_and(|start| start >= position) {
+            self.clip_in = ClockTime::NONE;
         }
         Ok(())
     }
 
-    /// Get the current volume (0.0 to 1.0)
+    /// Range of the clip to export. Unset points default to the start and the end
+    /// of the media.
+    pub fn clip_range(&self) -> Result<(ClockTime, ClockTime), MediaPlayerErrors> {
+        let start = self.clip_in.unwrap_or(ClockTime::ZERO);
+        let end = self
+            .clip_out
+            .or(self.duration)
+            .ok_or(MediaPlayerErrors::ErrorInvalidClip)?;
+        if end <= start {
+            return Err(MediaPlayerErrors::ErrorInvalidClip);
+        }
+        Ok((start, end))
+    }
+
+    /// Set the perceptual volume (0.0 to 1.0, or 2.0 when the boost is enabled)
+    pub fn set_volume(&mut self, volume: f64) -> Result<(), MediaPlayerErrors> {
+        let clamped_volume = volume.clamp(0.0, self.max_volume);
+        self.apply_volume(clamped_volume);
+        Ok(())
+    }
+
+    /// Get the current perceptual volume (0.0 to `max_volume`), as reported by playbin
     pub fn get_volume(&self) -> f64 {
-        self.volume
+        let linear = self.playbin.property::<f64>("volume")
+            * self.audio_filter.boost.property::<f64>("volume");
+        if linear <= 1.0 {
+            StreamVolume::convert_volume(
+                StreamVolumeFormat::Linear,
+                StreamVolumeFormat::Cubic,
+                linear,
+            )
+        } else {
+            linear
+        }
+    }
+
+    /// Get the current volume as a gain in dB
+    pub fn get_volume_db(&self) -> f64 {
+        20.0 * Self::linear_gain(self.get_volume()).log10()
+    }
+
+    pub fn max_volume(&self) -> f64 {
+        self.max_volume
+    }
+
+    /// Allow the volume to go up to 200%. Disabling the boost brings the volume back to 100%.
+    pub fn set_volume_boost(&mut self, enabled: bool) -> Result<(), MediaPlayerErrors> {
+        self.max_volume = if enabled { 2.0 } else { 1.0 };
+        self.set_volume(self.get_volume())
+    }
+
+    /// Linear gain for a perceptual volume. Up to 100% the cubic curve is used,
+    /// above it the volume is applied as a plain amplification factor.
+    fn linear_gain(volume: f64) -> f64 {
+        if volume <= 1.0 {
+            StreamVolume::convert_volume(
+                StreamVolumeFormat::Cubic,
+                StreamVolumeFormat::Linear,
+                volume,
+            )
+        } else {
+            volume
+        }
+    }
+
+    /// Split the volume between playbin, which handles attenuation, and the boost element
+    /// of the audio filter, which handles amplification ahead of the limiter
+    fn apply_volume(&self, volume: f64) {
+        let linear = Self::linear_gain(volume);
+        self.playbin.set_property("volume", linear.min(1.0));
+        self.audio_filter
+            .boost
+            .set_property("volume", linear.max(1.0));
     }
 
     /// Toggle mute/unmute
     pub fn toggle_mute(&mut self) -> Result<(), MediaPlayerErrors> {
-        if self.muted {
-            // Unmute: restore previous volume
-            self.playbin.set_property("volume", self.volume_before_mute);
-            self.volume = self.volume_before_mute;
-            self.muted = false;
+        self.playbin.set_property("mute", !self.is_muted());
+        Ok(())
+    }
+
+    /// Enable the loudness normalization. Must be called before the playback starts,
+    /// as playbin only accepts a new `audio-filter` in the `Null` or `Ready` states.
+    pub fn set_normalization(
+        &mut self,
+        mode: NormalizationMode,
+        target_lufs: f64,
+    ) -> Result<(), MediaPlayerErrors> {
+        self.normalization = mode;
+        self.target_lufs = target_lufs;
+
+        let audio_filter =
+            AudioFilter::build(mode, target_lufs).map_err(MediaPlayerErrors::ErrorNormalization)?;
+        let volume = self.get_volume();
+        self.playbin.set_property("audio-filter", &audio_filter.bin);
+        self.audio_filter = audio_filter;
+        self.apply_volume(volume);
+        Ok(())
+    }
+
+    /// Seek to the start of the chapter at `index`
+    pub fn seek_chapter(&self, index: usize) -> Result<(), MediaPlayerErrors> {
+        let chapter = self
+            .chapters
+            .get(index)
+            .ok_or(MediaPlayerErrors::ErrorInvalidChapter(index))?;
+        self.seek_position(chapter.start)?;
+        self.show_osd(if chapter.title.is_empty() {
+            format!("Chapter {}", index + 1)
+        } else {
+            format!("Chapter {}: {}", index + 1, chapter.title)
+        });
+        Ok(())
+    }
+
+    pub fn next_chapter(&self) -> Result<(), MediaPlayerErrors> {
+        let next = self.current_chapter().map_or(0, |index| index + 1);
+        if next >= self.chapters.len() {
+            return Ok(());
+        }
+        self.seek_chapter(next)
+    }
+
+    /// Go back to the start of the current chapter, or to the previous one
+    /// when the current chapter started less than 3 seconds ago
+    pub fn previous_chapter(&self) -> Result<(), MediaPlayerErrors> {
+        let Some(current) = self.current_chapter() else {
+            return Ok(());
+        };
+        let position = self.get_position()?;
+        if position > self.chapters[current].start + 3 * ClockTime::SECOND || current == 0 {
+            self.seek_chapter(current)
         } else {
-            // Mute: save current volume and set to 0
-            self.volume_before_mute = self.volume;
-            self.playbin.set_property("volume", 0.0f64);
-            self.volume = 0.0;
-            self.muted = true;
+            self.seek_chapter(current - 1)
+        }
+    }
+
+    /// Configure the source element created by playbin for `origin`. Live origins
+    /// disable seeking and always play at the live edge, since they produce no data
+    /// while paused.
+    pub fn set_origin(&mut self, origin: &OriginType) {
+        self.live_origin = origin.is_live();
+        self.is_live = self.live_origin;
+        if self.live_origin {
+            self.seek_enabled = false;
+        }
+
+        // udpsrc has no jitter buffer of its own, the latency is set on the pipeline
+        if let Some(pipeline) = self.playbin.dynamic_cast_ref::<Pipeline>() {
+            pipeline.set_latency(match origin {
+                OriginType::Udp { latency, .. } => {
+                    Some(ClockTime::from_mseconds(u64::from(*latency)))
+                }
+                _ => ClockTime::NONE,
+            });
+        }
+
+        *self.origin.lock().unwrap() = origin.clone();
+    }
+
+    /// Replace the media being played, keeping the player settings. Media out of the queue
+    /// replace it.
+    pub fn open_uri(&mut self, uri: &str, origin: &OriginType) -> Result<(), MediaPlayerErrors> {
+        self.remember_position();
+        self.save_media_settings();
+        if !self.queue.entries.is_empty() && self.queue_entry().is_none_or(|entry| entry.uri != uri)
+        {
+            self.queue = Playlist::default();
+            self.queue_index = None;
+            self.emit(PlayerEvent::QueueChanged);
+        }
+        self.stop_player()?;
+        self.stop_recording();
+        self.playbin.set_property("uri", uri);
+        self.uri = uri.to_string();
+
+        self.duration = ClockTime::NONE;
+        self.seek_enabled = false;
+        self.has_replaygain_tags = false;
+        self.loudness_analysis_started = false;
+        // Untagged media play at the target loudness until their analysis ends
+        if let Some(rgvolume) = &self.audio_filter.rgvolume {
+            rgvolume.set_property(
+                "fallback-gain",
+                loudness::pre_amp_for_target(self.target_lufs),
+            );
+        }
+        self.loop_start = ClockTime::NONE;
+        self.loop_end = ClockTime::NONE;
+        self.clip_in = ClockTime::NONE;
+        self.clip_out = ClockTime::NONE;
+        self.chapters.clear();
+        self.buffering_percent = 100;
+        self.reconnect_attempts = 0;
+        self.reconnect_pending = false;
+        self.has_played = false;
+        self.resume_position = ClockTime::NONE;
+        self.in_history = false;
+        self.title = None;
+        self.demuxer = None;
+        self.variants.clear();
+        self.bandwidth = None;
+        self.video_decoder = None;
+        self.decoder_element = None;
+        self.video_balance = VideoBalance::load(&self.media_settings, uri);
+        self.video_filter.set_balance(&self.video_balance);
+        let av_offset = self.media_settings.int64(uri, AV_OFFSET_KEY).unwrap_or(0);
+        self.apply_av_offset(av_offset);
+        self.video_filter.set_transform(&VideoTransform::default());
+        self.cover_art = None;
+        self.audio_only = false;
+        self.set_origin(origin);
+        self.emit(PlayerEvent::MediaChanged);
+        self.emit(PlayerEvent::VideoBalanceChanged);
+        self.emit(PlayerEvent::VideoTransformChanged);
+        self.emit(PlayerEvent::VisualizationChanged);
+        self.emit(PlayerEvent::BookmarksChanged);
+        self.emit(PlayerEvent::WatchLaterChanged);
+
+        self.play_player()
+    }
+
+    /// Open a media of the history, resuming where its playback stopped
+    pub fn open_recent(&mut self, uri: &str) -> Result<(), MediaPlayerErrors> {
+        let origin = self.recent_origin(uri)?;
+        let position = self.history.entry(uri).and_then(|entry| entry.position);
+        self.open_uri(uri, &origin)?;
+        self.resume_position = position;
+        Ok(())
+    }
+
+    /// Origin the media was opened with, as saved in the history. Live media whose options
+    /// were not saved are refused, the others get the default options.
+    fn recent_origin(&self, uri: &str) -> Result<OriginType, MediaPlayerErrors> {
+        if let Some(origin) = self.history.entry(uri).and_then(|entry| entry.origin) {
+            return Ok(origin);
+        }
+        match OriginType::for_uri(uri) {
+            Some(origin) if origin.is_live() => {
+                Err(MediaPlayerErrors::ErrorUnsavedOrigin(uri.to_string()))
+            }
+            Some(origin) => Ok(origin),
+            None => Err(MediaPlayerErrors::ErrorUnsupportedUri(uri.to_string())),
+        }
+    }
+
+    pub fn queue(&self) -> &Playlist {
+        &self.queue
+    }
+
+    /// Index of the entry being played and length of the queue
+    pub fn queue_position(&self) -> Option<(usize, usize)> {
+        self.queue_index
+            .map(|index| (index, self.queue.entries.len()))
+    }
+
+    fn queue_entry(&self) -> Option<&PlaylistEntry> {
+        self.queue.entries.get(self.queue_index?)
+    }
+
+    /// Play the media of `playlist` one after the other, starting with the entry at `index`
+    pub fn set_queue(&mut self, playlist: Playlist, index: usize) -> Result<(), MediaPlayerErrors> {
+        self.queue = playlist;
+        self.play_queue_entry(index)
+    }
+
+    /// Play another entry of the queue
+    pub fn play_queue_entry(&mut self, index: usize) -> Result<(), MediaPlayerErrors> {
+        let Some(entry) = self.queue.entries.get(index).cloned() else {
+            return Ok(());
+        };
+        // Playlists have no origin options: reuse the ones saved in the history, if any
+        let origin = self
+            .history
+            .entry(&entry.uri)
+            .and_then(|recent| recent.origin)
+            .or_else(|| OriginType::for_uri(&entry.uri))
+            .ok_or_else(|| MediaPlayerErrors::ErrorUnsupportedUri(entry.uri.clone()))?;
+        self.queue_index = Some(index);
+        self.emit(PlayerEvent::QueueChanged);
+        self.show_osd(format!(
+            "{}/{} {}",
+            index + 1,
+            self.queue.entries.len(),
+            entry.title.unwrap_or_else(|| media_title(&entry.uri))
+        ));
+        self.open_uri(&entry.uri, &origin)
+    }
+
+    /// Play the next entry of the queue, if any
+    pub fn play_next(&mut self) -> Result<(), MediaPlayerErrors> {
+        match self.queue_index {
+            Some(index) if index + 1 < self.queue.entries.len() => self.play_queue_entry(index + 1),
+            _ => Ok(()),
+        }
+    }
+
+    /// Play the previous entry of the queue, if any
+    pub fn play_previous(&mut self) -> Result<(), MediaPlayerErrors> {
+        match self.queue_index {
+            Some(index) if index > 0 => self.play_queue_entry(index - 1),
+            _ => Ok(()),
+        }
+    }
+
+    /// Save the queue as a playlist, or the media being played when there is no queue.
+    /// The format follows the extension of `path`.
+    pub fn save_queue(&self, path: &Path) -> Result<(), MediaPlayerErrors> {
+        if !self.queue.entries.is_empty() {
+            return self.queue.save(path);
+        }
+        let playlist = Playlist {
+            entries: vec![PlaylistEntry {
+                title: self.title.clone(),
+                duration: self.duration,
+                ..PlaylistEntry::new(self.uri.clone())
+            }],
+        };
+        playlist.save(path)
+    }
+
+    /// Open a capture device, which plays as a live source
+    pub fn open_device(&mut self, device: &Device) -> Result<(), MediaPlayerErrors> {
+        let uri = devices::device_uri(device).ok_or_else(|| {
+            MediaPlayerErrors::ErrorUnplayableDevice(device.display_name().into())
+        })?;
+        self.open_uri(&uri, &OriginType::Device)
+    }
+
+    /// Configure the playbin buffering of network streams.
+    /// `size` is in bytes, `None` keeps playbin's defaults.
+    pub fn set_buffering(&self, size: Option<i32>, duration: Option<ClockTime>) {
+        if let Some(size) = size {
+            self.playbin.set_property("buffer-size", size);
+        }
+        if let Some(duration) = duration {
+            self.playbin
+                .set_property("buffer-duration", duration.nseconds() as i64);
         }
+    }
+
+    /// Restart the stream after an exponential backoff delay, the position is restored
+    /// once it plays again
+    fn schedule_reconnect(&mut self) {
+        if self.resume_position.is_none() && self.seek_enabled {
+            self.resume_position = self.get_position().ok();
+        }
+        let delay = Duration::from_secs(1 << self.reconnect_attempts);
+        self.reconnect_attempts += 1;
+        self.reconnect_pending = true;
+        self.buffering_percent = 100;
+
+        // Going back to `Ready` closes the connection of the source
+        self.playbin.set_state(State::Ready).ok();
+        self.emit(PlayerEvent::Reconnecting {
+            attempt: self.reconnect_attempts,
+        });
+
+        let bus = self.get_bus();
+        glib::timeout_add_local_once(delay, move || {
+            bus.post(message::Application::new(Structure::new_empty(RECONNECT)))
+                .ok();
+        });
+    }
+
+    /// Prefer, forbid or require the hardware video decoders. The decoder ranks are global,
+    /// so this applies to the next media opened.
+    pub fn set_hardware_decoding(&mut self, mode: HardwareDecoding) {
+        decoders::apply_hardware_decoding(mode);
+        self.hardware_decoding = mode;
+    }
+
+    /// Play the media again with software decoders, after the hardware decoder failed
+    fn fall_back_to_software_decoding(&mut self) {
+        if self.resume_position.is_none() && self.seek_enabled {
+            self.resume_position = self.get_position().ok();
+        }
+        self.set_hardware_decoding(HardwareDecoding::Forbid);
+        self.video_decoder = None;
+        self.decoder_element = None;
+
+        // Going back to `Ready` removes the decoders, they are plugged again on the way up
+        self.playbin.set_state(State::Ready).ok();
+        self.playbin.set_state(self.target_state.get()).ok();
+    }
+
+    /// Select the quality of adaptive streams. Locks are applied as soon as the manifest
+    /// is known, so this can be called before the playback starts.
+    pub fn set_quality(&mut self, quality: QualityLock) {
+        self.quality = quality;
+        if let Some(demuxer) = &self.demuxer {
+            adaptive::apply_quality_lock(demuxer, &self.variants, quality);
+        }
+    }
+
+    pub fn set_recording_settings(&mut self, settings: RecordingSettings) {
+        self.recording_settings = settings;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// File being written and time since the recording started
+    pub fn recording_status(&self) -> Option<(&PathBuf, Duration)> {
+        self.recorder
+            .as_ref()
+            .map(|recorder| (recorder.path(), recorder.elapsed()))
+    }
+
+    /// Record the media being played to a file named after the recording settings.
+    /// Streams are muxed as they are when the format allows it, otherwise transcoded.
+    /// Before the media plays, the recording starts once its streams are known.
+    pub fn start_recording(&mut self) -> Result<(), MediaPlayerErrors> {
+        if self.recorder.is_some() {
+            return Ok(());
+        }
+        let taps = self.recording_taps();
+        if taps.is_empty() && !self.playing {
+            self.recording_pending = true;
+            return Ok(());
+        }
+        self.recording_pending = false;
+        let recorder = Recorder::start(&self.uri, &taps, &self.recording_settings, self.get_bus())?;
+        self.recorder = Some(recorder);
+        self.emit(PlayerEvent::RecordingChanged { recording: true });
         Ok(())
     }
 
+    /// Pads carrying the streams being played: the inputs of the audio and video decoders,
+    /// or the inputs of the filters for raw streams such as those of capture devices
+    fn recording_taps(&self) -> Vec<Pad> {
+        let mut taps = Vec::new();
+        let (mut has_audio, mut has_video) = (false, false);
+        if let Some(bin) = self.playbin.dynamic_cast_ref::<Bin>() {
+            for element in bin.iterate_recurse().into_iter().flatten() {
+                let Some(klass) = element
+                    .factory()
+                    .and_then(|factory| factory.metadata("klass").map(|klass| klass.to_string()))
+                else {
+                    continue;
+                };
+                let is_audio = klass.contains("Decoder/Audio");
+                let is_video = klass.contains("Decoder/Video");
+                if (is_audio || is_video)
+                    && let Some(pad) = element.static_pad("sink")
+                {
+                    has_audio |= is_audio;
+                    has_video |= is_video;
+                    taps.push(pad);
+                }
+            }
+        }
+        if !has_audio
+            && self.playbin.property::<i32>("n-audio") > 0
+            && let Some(pad) = self.audio_filter.bin.static_pad("sink")
+        {
+            taps.push(pad);
+        }
+        if !has_video
+            && self.playbin.property::<i32>("n-video") > 0
+            && let Some(pad) = self.video_filter.bin.static_pad("sink")
+        {
+            taps.push(pad);
+        }
+        taps
+    }
+
+    /// Finish the recording. The file is complete once [`PlayerEvent::RecordingChanged`]
+    /// is emitted.
+    pub fn stop_recording(&mut self) {
+        self.recording_pending = false;
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if recorder.stopping() {
+            return;
+        }
+        recorder.stop();
+
+        // A source that stopped sending data may never let the end of stream through
+        let bus = self.get_bus();
+        glib::timeout_add_local_once(RECORDING_STOP_TIMEOUT, move || {
+            bus.post(message::Application::new(Structure::new_empty(
+                RECORDING_ENDED,
+            )))
+            .ok();
+        });
+    }
+
+    pub fn toggle_recording(&mut self) -> Result<(), MediaPlayerErrors> {
+        if self.is_recording() || self.recording_pending {
+            self.stop_recording();
+            Ok(())
+        } else {
+            self.start_recording()
+        }
+    }
+
     /// Check if the player is muted
     pub fn is_muted(&self) -> bool {
-        self.muted
+        self.playbin.property::<bool>("mute")
+    }
+
+    /// Register a callback for the player events.
+    /// Callbacks run from the main loop once the player is no longer borrowed.
+    pub fn connect_event(&self, callback: impl Fn(&PlayerEvent) + 'static) {
+        self.listeners.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Media opened recently, most recent first
+    pub fn recent_media(&self) -> Vec<RecentMedia> {
+        self.history.entries()
+    }
+
+    pub fn is_history_enabled(&self) -> bool {
+        self.history.is_enabled()
+    }
+
+    /// Start or stop remembering the opened media. Disabling also clears the history.
+    pub fn set_history_enabled(&mut self, enabled: bool) -> Result<(), MediaPlayerErrors> {
+        self.history.set_enabled(enabled);
+        if enabled {
+            self.add_to_history();
+        }
+        self.emit(PlayerEvent::HistoryChanged);
+        self.history.save()
+    }
+
+    pub fn clear_history(&mut self) -> Result<(), MediaPlayerErrors> {
+        self.history.clear();
+        self.emit(PlayerEvent::HistoryChanged);
+        self.history.save()
+    }
+
+    /// Put the media at the top of the history once it plays. Capture devices are left out.
+    fn add_to_history(&mut self) {
+        if self.uri.is_empty() || matches!(*self.origin.lock().unwrap(), OriginType::Device) {
+            return;
+        }
+        let origin = self.origin.lock().unwrap().clone();
+        self.history.add(&self.uri, self.title.as_deref(), &origin);
+        self.in_history = true;
+        self.emit(PlayerEvent::HistoryChanged);
+        if let Err(err) = self.history.save() {
+            println!("{}", err);
+        }
+    }
+
+    /// Save the playback position of the media in the history, to resume it when opened
+    /// again. Media played to the end start over.
+    pub fn remember_position(&self) {
+        if self.uri.is_empty() || !self.seek_enabled || self.is_live {
+            return;
+        }
+        let position = self.resume_point();
+        if self.in_history {
+            self.history.set_position(&self.uri, position);
+            if let Err(err) = self.history.save() {
+                println!("{}", err);
+            }
+        }
+        if self.bookmarks.is_watch_later(&self.uri) {
+            self.bookmarks.set_position(&self.uri, position);
+            if let Err(err) = self.bookmarks.save() {
+                println!("{}", err);
+            }
+        }
+    }
+
+    /// Position to resume the playback from, `None` near the end
+    fn resume_point(&self) -> Option<ClockTime> {
+        self.get_position().ok().filter(|position| {
+            self.duration
+                .is_none_or(|duration| *position + RESTART_MARGIN < duration)
+        })
+    }
+
+    /// Bookmarks of the media, sorted by position
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        self.bookmarks.bookmarks(&self.uri)
+    }
+
+    /// Bookmark the current position, named after the chapter or else the time. Returns
+    /// the index of the new bookmark.
+    pub fn add_bookmark_here(&mut self) -> Result<usize, MediaPlayerErrors> {
+        let position = self.get_position()?;
+        let name = self
+            .current_chapter()
+            .map(|index| self.chapters[index].title.clone())
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| format!("Bookmark at {}", short_time(position)));
+
+        let mut bookmarks = self.bookmarks();
+        let index = bookmarks.partition_point(|bookmark| bookmark.position <= position);
+        bookmarks.insert(
+            index,
+            Bookmark {
+                position,
+                name,
+                note: String::new(),
+            },
+        );
+        self.set_bookmarks(&bookmarks)?;
+        self.show_osd(format!("Bookmark added at {}", short_time(position)));
+        Ok(index)
+    }
+
+    /// Rename a bookmark and replace its note
+    pub fn update_bookmark(
+        &mut self,
+        index: usize,
+        name: &str,
+        note: &str,
+    ) -> Result<(), MediaPlayerErrors> {
+        let mut bookmarks = self.bookmarks();
+        let bookmark = bookmarks
+            .get_mut(index)
+            .ok_or(MediaPlayerErrors::ErrorInvalidBookmark(index))?;
+        bookmark.name = name.to_string();
+        bookmark.note = note.to_string();
+        self.set_bookmarks(&bookmarks)
+    }
+
+    pub fn remove_bookmark(&mut self, index: usize) -> Result<(), MediaPlayerErrors> {
+        let mut bookmarks = self.bookmarks();
+        if index >= bookmarks.len() {
+            return Err(MediaPlayerErrors::ErrorInvalidBookmark(index));
+        }
+        bookmarks.remove(index);
+        self.set_bookmarks(&bookmarks)
+    }
+
+    pub fn seek_bookmark(&self, index: usize) -> Result<(), MediaPlayerErrors> {
+        let bookmark = self
+            .bookmarks()
+            .into_iter()
+            .nth(index)
+            .ok_or(MediaPlayerErrors::ErrorInvalidBookmark(index))?;
+        self.seek_position(bookmark.position)?;
+        self.show_osd(bookmark.name);
+        Ok(())
+    }
+
+    /// Write the bookmarks to `path`, as JSON or as WebVTT chapters for `.vtt` files
+    pub fn export_bookmarks(&self, path: &Path) -> Result<(), MediaPlayerErrors> {
+        bookmarks::export(&self.uri, &self.bookmarks(), self.duration, path)
+    }
+
+    /// Add the bookmarks of a JSON or WebVTT file, skipping the ones already there. Returns
+    /// how many were added.
+    pub fn import_bookmarks(&mut self, path: &Path) -> Result<usize, MediaPlayerErrors> {
+        let mut bookmarks = self.bookmarks();
+        let known = bookmarks.len();
+        for bookmark in bookmarks::import(path)? {
+            if !bookmarks.contains(&bookmark) {
+                bookmarks.push(bookmark);
+            }
+        }
+        let added = bookmarks.len() - known;
+        bookmarks.sort_by_key(|bookmark| bookmark.position);
+        self.set_bookmarks(&bookmarks)?;
+        self.show_osd(format!("{} bookmarks imported", added));
+        Ok(added)
+    }
+
+    fn set_bookmarks(&mut self, bookmarks: &[Bookmark]) -> Result<(), MediaPlayerErrors> {
+        self.bookmarks.set_bookmarks(&self.uri, bookmarks);
+        self.emit(PlayerEvent::BookmarksChanged);
+        self.bookmarks.save()
+    }
+
+    pub fn is_watch_later(&self) -> bool {
+        self.bookmarks.is_watch_later(&self.uri)
+    }
+
+    /// Put the media aside to watch later, or take it back. It resumes where it stopped
+    /// when opened from the list.
+    pub fn set_watch_later(&mut self, watch_later: bool) -> Result<(), MediaPlayerErrors> {
+        if self.uri.is_empty() {
+            return Ok(());
+        }
+        let origin = self.origin.lock().unwrap().clone();
+        self.bookmarks
+            .set_watch_later(&self.uri, self.title.as_deref(), &origin, watch_later);
+        if watch_later && self.seek_enabled && !self.is_live {
+            self.bookmarks.set_position(&self.uri, self.resume_point());
+        }
+        self.show_osd(if watch_later {
+            "Added to Watch Later"
+        } else {
+            "Removed from Watch Later"
+        });
+        self.emit(PlayerEvent::WatchLaterChanged);
+        self.bookmarks.save()
+    }
+
+    /// Media put aside to watch later
+    pub fn watch_later(&self) -> Vec<WatchLater> {
+        self.bookmarks.watch_later()
+    }
+
+    /// Open a media put aside to watch later, resuming where its playback stopped
+    pub fn open_watch_later(&mut self, uri: &str) -> Result<(), MediaPlayerErrors> {
+        let Some(media) = self.bookmarks.watch_later_entry(uri) else {
+            return self.open_recent(uri);
+        };
+        let origin = match media.origin {
+            Some(origin) => origin,
+            None => self.recent_origin(uri)?,
+        };
+        self.open_uri(uri, &origin)?;
+        self.resume_position = media.position;
+        Ok(())
+    }
+
+    /// Show a short message over the video, e.g. "Speed 1.5x". Any command can report
+    /// its effect this way, the window shows it as [`PlayerEvent::Osd`].
+    pub fn show_osd(&self, message: impl Into<String>) {
+        self.emit(PlayerEvent::Osd(message.into()));
+    }
+
+    fn emit(&self, event: PlayerEvent) {
+        let listeners = self.listeners.clone();
+        glib::idle_add_local_once(move || {
+            for listener in listeners.borrow().iter() {
+                listener(&event);
+            }
+        });
+    }
+}
+
+/// Set or clear one of playbin's `flags`, by nick, e.g. `deinterlace`
+pub(crate) fn set_play_flag(playbin: &Element, nick: &str, enabled: bool) {
+    let flags = playbin.property_value("flags");
+    let Some(flags_class) = glib::FlagsClass::with_type(flags.type_()) else {
+        return;
+    };
+    let Some(builder) = flags_class.builder_with_value(flags) else {
+        return;
+    };
+    let builder = if enabled {
+        builder.set_by_nick(nick)
+    } else {
+        builder.unset_by_nick(nick)
+    };
+    if let Some(flags) = builder.build() {
+        playbin.set_property_from_value("flags", &flags);
+    }
+}
+
+/// Position as `m:ss`, or `h:mm:ss` past an hour
+pub fn short_time(time: ClockTime) -> String {
+    let (hours, minutes, seconds) = (time.hours(), time.minutes() % 60, time.seconds() % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
     }
 }
 
 impl Drop for MediaPlayer {
     fn drop(&mut self) {
+        self.remember_position();
+        self.save_media_settings();
+        if let Some(monitor) = &self.audio_monitor {
+            monitor.stop();
+        }
         self.playbin
             .set_state(State::Null)
             .expect("Unable to set the playbin to the `Null` state");
@@ -222,12 +1634,83 @@ pub enum MediaPlayerErrors {
     #[error("Unable to get position in this media. Check if you're using a stream")]
     ErrorGettingPosition,
 
+    #[error("The end of the loop must come after its start")]
+    ErrorInvalidLoop,
+
+    #[error("The end of the clip must come after its start")]
+    ErrorInvalidClip,
+
+    #[error("The media has no chapter {0}")]
+    ErrorInvalidChapter(usize),
+
+    #[error("The device {0} cannot be played")]
+    ErrorUnplayableDevice(String),
+
+    #[error("Unable to open {0}, its origin is unknown")]
+    ErrorUnsupportedUri(String),
+
+    #[error("Unable to open {0} again, its options or password were not saved")]
+    ErrorUnsavedOrigin(String),
+
+    #[error("Unable to read the playlist: {0}")]
+    ErrorReadingPlaylist(glib::Error),
+
+    #[error("{0} is not a playlist, or it is empty")]
+    ErrorInvalidPlaylist(String),
+
+    #[error("Unable to save the playlist to {}: {1}", .0.display())]
+    ErrorSavingPlaylist(PathBuf, std::io::Error),
+
+    #[error("The media has no bookmark {0}")]
+    ErrorInvalidBookmark(usize),
+
+    #[error("Unable to read the bookmarks of {}: {1}", .0.display())]
+    ErrorReadingBookmarks(PathBuf, std::io::Error),
+
+    #[error("{} holds no bookmark in JSON or WebVTT", .0.display())]
+    ErrorInvalidBookmarks(PathBuf),
+
+    #[error("Unable to save the bookmarks to {}: {1}", .0.display())]
+    ErrorSavingBookmarks(PathBuf, std::io::Error),
+
     #[error("Error playing media")]
     ErrorPlaying(StateChangeError),
     #[error("Error stopping media")]
     Errorstopping(StateChangeError),
     #[error("Error pausing media")]
     ErrorPausing(StateChangeError),
+
+    #[error("Unable to set up the loudness normalization: {0}")]
+    ErrorNormalization(glib::Error),
+    #[error("Unable to set up the loudness analysis: {0}")]
+    ErrorLoudnessAnalysis(glib::error::BoolError),
+    #[error("Error starting the loudness analysis")]
+    ErrorStartingLoudnessAnalysis(StateChangeError),
+    #[error("Unable to measure the loudness: {0}")]
+    ErrorMeasuringLoudness(glib::Error),
+    #[error("The loudness analysis found no audio to measure")]
+    ErrorNoLoudness,
+
+    #[error("Unable to save the settings: {0}")]
+    ErrorSavingSettings(glib::Error),
+
+    #[error("Unable to use the audio output: {0}")]
+    ErrorAudioDevice(glib::error::BoolError),
+
+    #[error("Unable to set up the visualization: {0}")]
+    ErrorVisualization(glib::error::BoolError),
+
+    #[error("Unable to set up the recording: {0}")]
+    ErrorRecording(glib::error::BoolError),
+    #[error("Error starting the recording")]
+    ErrorStartingRecording(StateChangeError),
+    #[error("No audio or video stream to record")]
+    ErrorNothingToRecord,
+
+    #[error("Unable to set up the transcoding: {0}")]
+    ErrorTranscoding(glib::error::BoolError),
+    #[error("Error starting the transcoding")]
+    ErrorStartingTranscoding(StateChangeError),
 }
 
 pub fn handle_message(mut media_player: RefMut<'_, MediaPlayer>, msg: &Message) {
@@ -240,6 +1723,233 @@ pub fn handle_message(mut media_player: RefMut<'_, MediaPlayer>, msg: &Message)
                 err.error(),
                 err.debug()
             );
+
+            // A failing hardware decoder is replaced by a software one, unless required
+            let from_hardware_decoder = media_player
+                .video_decoder
+                .as_ref()
+                .is_some_and(|decoder| decoder.hardware)
+                && media_player
+                    .decoder_element
+                    .as_ref()
+                    .zip(err.src())
+                    .is_some_and(|(decoder, src)| {
+                        src == decoder.upcast_ref::<Object>() || src.has_as_ancestor(decoder)
+                    });
+            if from_hardware_decoder && media_player.hardware_decoding != HardwareDecoding::Require
+            {
+                media_player.fall_back_to_software_decoding();
+                return;
+            }
+
+            // Network streams may drop mid-stream, try to reconnect before giving up. Errors
+            // opening the stream, or from the demuxers and decoders, are not retried.
+            let from_source = media_player
+                .playbin
+                .property::<Option<Element>>("source")
+                .zip(err.src())
+                .is_some_and(|(source, src)| {
+                    src == source.upcast_ref::<Object>() || src.has_as_ancestor(&source)
+                });
+            if media_player.is_network_stream()
+                && media_player.has_played
+                && from_source
+                && !media_player.reconnect_pending
+                && media_player.reconnect_attempts < MAX_RECONNECT_ATTEMPTS
+            {
+                media_player.schedule_reconnect();
+            }
+        }
+        MessageView::Tag(tag) => {
+            let tag_list = tag.tags();
+            if tag_list.get::<tags::TrackGain>().is_some()
+                || tag_list.get::<tags::AlbumGain>().is_some()
+            {
+                media_player.has_replaygain_tags = true;
+            }
+            // Tags are usually read before the media plays and enters the history
+            if let Some(title) = tag_list.get::<tags::Title>()
+                && media_player.title.as_deref() != Some(title.get())
+            {
+                media_player.title = Some(title.get().to_string());
+                if let Some(index) = media_player.queue_index
+                    && let Some(entry) = media_player.queue.entries.get_mut(index)
+                    && entry.title.is_none()
+                {
+                    entry.title = media_player.title.clone();
+                }
+                if media_player.in_history {
+                    media_player
+                        .history
+                        .set_title(&media_player.uri, title.get());
+                    media_player.emit(PlayerEvent::HistoryChanged);
+                }
+            }
+            // The first image is usually the front cover
+            if media_player.cover_art.is_none()
+                && let Some(cover_art) = visualization::cover_art(&tag_list)
+            {
+                media_player.cover_art = Some(cover_art);
+                media_player.emit(PlayerEvent::VisualizationChanged);
+            }
+        }
+        MessageView::Application(application) => {
+            let Some(structure) = application.structure() else {
+                return;
+            };
+            if structure.has_name(MANIFEST_RECEIVED) {
+                let demuxer = application
+                    .src()
+                    .and_then(|src| src.downcast_ref::<Element>())
+                    .cloned();
+                if let (Some(demuxer), Ok(manifest)) = (demuxer, structure.get::<&str>("manifest"))
+                {
+                    media_player.variants = adaptive::parse_variants(manifest);
+                    adaptive::apply_quality_lock(
+                        &demuxer,
+                        &media_player.variants,
+                        media_player.quality,
+                    );
+                    media_player.demuxer = Some(demuxer);
+                    media_player.emit(PlayerEvent::VariantsChanged);
+                }
+            } else if structure.has_name(DECODER_SELECTED) {
+                let decoder = application
+                    .src()
+                    .and_then(|src| src.downcast_ref::<Element>())
+                    .cloned();
+                if let (Ok(name), Ok(hardware)) = (
+                    structure.get::<&str>("name"),
+                    structure.get::<bool>("hardware"),
+                ) {
+                    media_player.video_decoder = Some(VideoDecoder {
+                        name: name.to_string(),
+                        hardware,
+                    });
+                    media_player.decoder_element = decoder;
+                    media_player.emit(PlayerEvent::DecoderChanged);
+                }
+            } else if structure.has_name(AUDIO_DEVICES_CHANGED) {
+                // The output playing the media was unplugged, go back to the default one
+                if let Ok(removed) = structure.get::<&str>("removed")
+                    && media_player
+                        .audio_device
+                        .as_ref()
+                        .is_some_and(|device| device.display_name() == removed)
+                {
+                    media_player.show_osd(format!("{} was unplugged", removed));
+                    if let Err(err) = media_player.set_audio_device(None) {
+                        println!("{}", err);
+                    }
+                }
+                media_player.emit(PlayerEvent::AudioDevicesChanged);
+            } else if structure.has_name(ORIENTATION_CHANGED) {
+                media_player.emit(PlayerEvent::VideoTransformChanged);
+            } else if structure.has_name(SAVE_MEDIA_SETTINGS) {
+                media_player.save_media_settings();
+            } else if structure.has_name(RECONNECT) {
+                media_player.reconnect_pending = false;
+                let target_state = media_player.target_state.get();
+                media_player.playbin.set_state(target_state).ok();
+            } else if structure.has_name(VOLUME_CHANGED) {
+                media_player.emit(PlayerEvent::VolumeChanged {
+                    volume: media_player.get_volume(),
+                    muted: media_player.is_muted(),
+                });
+            } else if structure.has_name(RECORDING_ENDED) {
+                // Posted once the file is written, when the recording failed, or when
+                // a stopped recording did not finish in time
+                let Some(recorder) = &media_player.recorder else {
+                    return;
+                };
+                match structure.get::<&str>("error") {
+                    Ok(error) => println!(
+                        "Recording to {} failed: {}",
+                        recorder.path().display(),
+                        error
+                    ),
+                    Err(_) if recorder.stopping() || structure.has_field("eos") => {
+                        println!("Recording saved to {}", recorder.path().display())
+                    }
+                    Err(_) => return,
+                }
+                media_player.recorder = None;
+                media_player.emit(PlayerEvent::RecordingChanged { recording: false });
+            } else if structure.has_name(LOUDNESS_MEASURED) {
+                // The analysis of a media opened before does not apply to this one
+                if structure.get::<&str>("uri").ok() != Some(media_player.uri.as_str()) {
+                    return;
+                }
+                if let Ok(error) = structure.get::<&str>("error") {
+                    println!("{}", error);
+                } else if let (Some(rgvolume), Ok(gain)) = (
+                    &media_player.audio_filter.rgvolume,
+                    structure.get::<f64>("gain"),
+                ) {
+                    // rgvolume does not add the pre-amp to the fallback gain
+                    let pre_amp = loudness::pre_amp_for_target(media_player.target_lufs);
+                    rgvolume.set_property("fallback-gain", gain + pre_amp);
+                }
+            }
+        }
+        MessageView::Element(element) => {
+            // Posted by adaptive demuxers after each fragment download
+            if let Some(structure) = element.structure()
+                && structure.has_name("adaptive-streaming-statistics")
+                && let (Ok(size), Ok(download_time)) = (
+                    structure.get::<u64>("fragment-size"),
+                    structure.get::<ClockTime>("fragment-download-time"),
+                )
+                && download_time > ClockTime::ZERO
+            {
+                media_player.bandwidth =
+                    Some((size as f64 * 8.0 / download_time.seconds_f64()) as u64);
+            }
+        }
+        MessageView::Buffering(buffering) => {
+            // Live sources cannot be paused to fill their buffer
+            if media_player.is_live {
+                return;
+            }
+            let percent = buffering.percent();
+            let target_state = media_player.target_state.get();
+            if percent < 100 {
+                media_player.playbin.set_state(State::Paused).ok();
+            } else if percent == 100 && target_state == State::Playing {
+                media_player.playbin.set_state(State::Playing).ok();
+            }
+            media_player.buffering_percent = percent;
+            media_player.emit(PlayerEvent::Buffering(percent));
+        }
+        MessageView::Toc(toc) => {
+            let (toc, _) = toc.toc();
+            let mut chapters = Vec::new();
+            collect_chapters(toc.entries(), &mut chapters);
+            chapters.sort_by_key(|chapter| chapter.start);
+            media_player.chapters = chapters;
+            media_player.emit(PlayerEvent::ChaptersChanged);
+        }
+        MessageView::Eos(_) => {
+            // The queue goes on with its next entry
+            if let Err(err) = media_player.play_next() {
+                println!("{}", err);
+            }
+        }
+        MessageView::SegmentDone(_) => {
+            // Jump back to point A without flushing, so the loop plays seamlessly
+            if let Some((start, end)) = media_player.active_loop() {
+                media_player
+                    .playbin
+                    .seek(
+                        1.0,
+                        SeekFlags::SEGMENT | SeekFlags::ACCURATE,
+                        SeekType::Set,
+                        start,
+                        SeekType::Set,
+                        end,
+                    )
+                    .ok();
+            }
         }
         MessageView::DurationChanged(_) => {
             // The duration has changed, mark the current one as invalid
@@ -257,12 +1967,62 @@ pub fn handle_message(mut media_player: RefMut<'_, MediaPlayer>, msg: &Message)
                 if media_player.playing {
                     if media_player.duration == gstreamer::ClockTime::NONE {
                         media_player.duration = media_player.playbin.query_duration();
+                        if media_player.duration.is_some() {
+                            media_player.emit(PlayerEvent::DurationChanged);
+                        }
                     }
 
                     let mut seeking = query::Seeking::new(Format::Time);
                     if media_player.playbin.query(&mut seeking) {
                         let (seekable, _, _) = seeking.result();
-                        media_player.seek_enabled = seekable;
+                        // RTSP VOD servers or SRT senders may answer the query, but live
+                        // origins are always played at the live edge
+                        media_player.seek_enabled = seekable && !media_player.live_origin;
+                    }
+
+                    let mut latency = query::Latency::new();
+                    if media_player.playbin.query(&mut latency) {
+                        let (live, min_latency, _) = latency.result();
+                        media_player.is_live = live || media_player.live_origin;
+                        media_player.live_latency = live.then_some(min_latency);
+                    }
+                    media_player.emit(PlayerEvent::SeekingChanged {
+                        seekable: media_player.seek_enabled,
+                        live: media_player.is_live,
+                    });
+
+                    let audio_only = media_player.playbin.property::<i32>("n-video") == 0
+                        && media_player.playbin.property::<i32>("n-audio") > 0;
+                    if audio_only != media_player.audio_only {
+                        media_player.audio_only = audio_only;
+                        media_player.emit(PlayerEvent::VisualizationChanged);
+                    }
+
+                    if !media_player.in_history {
+                        media_player.add_to_history();
+                    }
+
+                    if media_player.recording_pending
+                        && let Err(err) = media_player.start_recording()
+                    {
+                        println!("{}", err);
+                    }
+
+                    // Back from a reconnection: the stream is healthy again
+                    media_player.has_played = true;
+                    media_player.reconnect_attempts = 0;
+                    if let Some(position) = media_player.resume_position.take() {
+                        media_player.seek_position(position).ok();
+                    }
+
+                    // Media without ReplayGain tags gets its loudness measured once
+                    if media_player.audio_filter.rgvolume.is_some()
+                        && !media_player.has_replaygain_tags
+                        && !media_player.loudness_analysis_started
+                        && media_player.uri.starts_with("file://")
+                    {
+                        media_player.loudness_analysis_started = true;
+                        loudness::spawn_analysis(media_player.uri.clone(), media_player.get_bus());
                     }
                 }
             }
@@ -270,3 +2030,81 @@ pub fn handle_message(mut media_player: RefMut<'_, MediaPlayer>, msg: &Message)
         _ => (),
     }
 }
+
+/// Flatten the chapter entries of a table of contents, editions may nest them
+fn collect_chapters(entries: Vec<TocEntry>, chapters: &mut Vec<Chapter>) {
+    for entry in entries {
+        if entry.entry_type() == TocEntryType::Chapter
+            && let Some((start, stop)) = entry.start_stop_times()
+        {
+            let title = entry
+                .tags()
+                .and_then(|tag_list| {
+                    tag_list
+                        .get::<tags::Title>()
+                        .map(|title| title.get().to_string())
+                })
+                .unwrap_or_else(|| format!("Chapter {}", chapters.len() + 1));
+            chapters.push(Chapter {
+                title,
+                start: ClockTime::from_nseconds(start.max(0) as u64),
+                end: (stop >= 0).then(|| ClockTime::from_nseconds(stop as u64)),
+            });
+        }
+        collect_chapters(entry.sub_entries(), chapters);
+    }
+}
+
+/// Apply the settings of `origin` to the source element created by playbin
+pub(crate) fn configure_source(source: &Element, origin: &OriginType) {
+    match origin {
+        OriginType::Rtsp {
+            transport,
+            user,
+            password,
+            latency,
+        } => {
+            source.set_property_from_str(
+                "protocols",
+                match transport {
+                    RtspTransport::Tcp => "tcp",
+                    RtspTransport::Udp => "udp",
+                },
+            );
+            source.set_property("latency", *latency);
+            if let Some(user) = user {
+                source.set_property("user-id", user.as_str());
+            }
+            if let Some(password) = password {
+                source.set_property("user-pw", password.as_str());
+            }
+        }
+        OriginType::Srt {
+            mode,
+            passphrase,
+            latency,
+        } => {
+            source.set_property_from_str(
+                "mode",
+                match mode {
+                    SrtMode::Caller => "caller",
+                    SrtMode::Listener => "listener",
+                },
+            );
+            source.set_property("latency", i32::try_from(*latency).unwrap_or(i32::MAX));
+            if let Some(passphrase) = passphrase {
+                source.set_property("passphrase", passphrase.as_str());
+            }
+        }
+        OriginType::Udp {
+            caps: Some(caps), ..
+        } => match caps.parse::<Caps>() {
+            Ok(caps) => source.set_property("caps", &caps),
+            Err(_) => println!("Invalid caps for the UDP source: {}", caps),
+        },
+        OriginType::File
+        | OriginType::Http
+        | OriginType::Device
+        | OriginType::Udp { caps: None, .. } => (),
+    }
+}
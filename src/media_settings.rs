@@ -0,0 +1,83 @@
+use {
+    crate::media_player::MediaPlayerErrors,
+    gstreamer::glib::{self, KeyFile, KeyFileFlags},
+    std::path::{Path, PathBuf},
+};
+
+/// Directory of the files saved by the player, e.g. `~/.config/aug-media-player`
+pub fn config_dir() -> PathBuf {
+    glib::user_config_dir().join("aug-media-player")
+}
+
+/// Settings remembered for each media, such as the video adjustments.
+/// Stored as a key file with one group per URI.
+pub struct MediaSettings {
+    path: PathBuf,
+    key_file: KeyFile,
+}
+
+impl MediaSettings {
+    /// Load the settings from `media.ini` in [`config_dir`]. A missing or unreadable file
+    /// gives empty settings.
+    pub fn load() -> Self {
+        Self::load_from(config_dir().join("media.ini"))
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let key_file = KeyFile::new();
+        key_file
+            .load_from_file(&path, KeyFileFlags::KEEP_COMMENTS)
+            .ok();
+        Self { path, key_file }
+    }
+
+    pub fn double(&self, uri: &str, key: &str) -> Option<f64> {
+        self.key_file.double(&uri_group(uri), key).ok()
+    }
+
+    pub fn set_double(&self, uri: &str, key: &str, value: f64) {
+        self.key_file.set_double(&uri_group(uri), key, value);
+    }
+
+    pub fn int64(&self, uri: &str, key: &str) -> Option<i64> {
+        self.key_file.int64(&uri_group(uri), key).ok()
+    }
+
+    pub fn set_int64(&self, uri: &str, key: &str, value: i64) {
+        self.key_file.set_int64(&uri_group(uri), key, value);
+    }
+
+    /// Forget a setting, and the media once it has no setting left
+    pub fn remove(&self, uri: &str, key: &str) {
+        let group = uri_group(uri);
+        self.key_file.remove_key(&group, key).ok();
+        if self.key_file.keys(&group).is_ok_and(|keys| keys.is_empty()) {
+            self.key_file.remove_group(&group).ok();
+        }
+    }
+
+    pub fn save(&self) -> Result<(), MediaPlayerErrors> {
+        save_key_file(&self.key_file, &self.path)
+    }
+}
+
+/// Group of a media in a key file. Group names cannot hold brackets nor line breaks, which
+/// URIs may contain.
+pub(crate) fn uri_group(uri: &str) -> String {
+    glib::Uri::escape_string(uri, None, true).to_string()
+}
+
+/// Write a key file, creating its directory if needed
+pub(crate) fn save_key_file(key_file: &KeyFile, path: &Path) -> Result<(), MediaPlayerErrors> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(|err| {
+            MediaPlayerErrors::ErrorSavingSettings(glib::Error::new(
+                glib::FileError::Failed,
+                &err.to_string(),
+            ))
+        })?;
+    }
+    key_file
+        .save_to_file(path)
+        .map_err(MediaPlayerErrors::ErrorSavingSettings)
+}
@@ -0,0 +1,533 @@
+use {
+    crate::media_player::MediaPlayerErrors,
+    clap::ValueEnum,
+    gstreamer::ClockTime,
+    gtk::{
+        gio::{self, prelude::*},
+        glib::{self, KeyFile, KeyFileFlags},
+    },
+    std::path::Path,
+};
+
+/// Playlist file formats
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// M3U, extended with `#EXTINF` titles and durations
+    M3u,
+    /// M3U encoded in UTF-8
+    M3u8,
+    /// PLS, an INI file with `FileN`, `TitleN` and `LengthN` keys
+    Pls,
+    /// XML Shareable Playlist Format
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Format matching the extension of `path`, e.g. `queue.xspf`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::from_str(extension, true).ok()
+    }
+}
+
+/// A media of a playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    pub uri: String,
+    pub title: Option<String>,
+    pub duration: Option<ClockTime>,
+}
+
+impl PlaylistEntry {
+    pub fn new(uri: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            title: None,
+            duration: None,
+        }
+    }
+}
+
+/// Media played one after the other
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Playlist {
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Format of the playlist at `uri`, `None` for other media. `.m3u8` files are HLS manifests
+/// as often as playlists, so only local ones are playlists.
+pub fn playlist_format(uri: &str) -> Option<PlaylistFormat> {
+    let path = uri.split(['?', '#']).next().unwrap_or(uri);
+    let format = PlaylistFormat::from_path(Path::new(path))?;
+    if format == PlaylistFormat::M3u8 && !uri.starts_with("file://") {
+        return None;
+    }
+    Some(format)
+}
+
+impl Playlist {
+    /// Read the playlist at `uri`, in the format given by its extension. `None` when `uri`
+    /// is not a local playlist, e.g. a remote one or an HLS manifest, which playbin plays.
+    pub fn load(uri: &str) -> Result<Option<Self>, MediaPlayerErrors> {
+        let Some(format) = playlist_format(uri).filter(|_| uri.starts_with("file://")) else {
+            return Ok(None);
+        };
+        let file = gio::File::for_uri(uri);
+        let (contents, _) = file
+            .load_contents(None::<&gio::Cancellable>)
+            .map_err(MediaPlayerErrors::ErrorReadingPlaylist)?;
+        let contents = String::from_utf8_lossy(&contents);
+
+        // Local manifests of live streams are not playlists of files
+        if contents.contains("#EXT-X-") {
+            return Ok(None);
+        }
+        let base = file.parent().unwrap_or(file);
+        let playlist = Self::parse(&contents, format, &base);
+        if playlist.entries.is_empty() {
+            return Err(MediaPlayerErrors::ErrorInvalidPlaylist(uri.to_string()));
+        }
+        Ok(Some(playlist))
+    }
+
+    /// Read a playlist, resolving the relative paths against the `base` directory
+    pub fn parse(contents: &str, format: PlaylistFormat, base: &gio::File) -> Self {
+        let entries = match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => parse_m3u(contents, base),
+            PlaylistFormat::Pls => parse_pls(contents, base),
+            PlaylistFormat::Xspf => parse_xspf(contents, base),
+        };
+        Self { entries }
+    }
+
+    /// Write the playlist in `format`. Local files are written as absolute paths, except
+    /// in XSPF which only holds URIs.
+    pub fn serialize(&self, format: PlaylistFormat) -> String {
+        match format {
+            PlaylistFormat::M3u | PlaylistFormat::M3u8 => self.to_m3u(),
+            PlaylistFormat::Pls => self.to_pls(),
+            PlaylistFormat::Xspf => self.to_xspf(),
+        }
+    }
+
+    /// Write the playlist to `path`, in the format given by its extension, M3U by default
+    pub fn save(&self, path: &Path) -> Result<(), MediaPlayerErrors> {
+        let format = PlaylistFormat::from_path(path).unwrap_or(PlaylistFormat::M3u);
+        std::fs::write(path, self.serialize(format))
+            .map_err(|err| MediaPlayerErrors::ErrorSavingPlaylist(path.to_path_buf(), err))
+    }
+
+    fn to_m3u(&self) -> String {
+        let mut m3u = String::from("#EXTM3U\n");
+        for entry in &self.entries {
+            if let Some(title) = &entry.title {
+                let seconds = entry
+                    .duration
+                    .map_or(-1, |duration| duration.seconds() as i64);
+                m3u.push_str(&format!("#EXTINF:{},{}\n", seconds, title));
+            }
+            m3u.push_str(&local_path(&entry.uri));
+            m3u.push('\n');
+        }
+        m3u
+    }
+
+    /// Values are written raw, without the key file escapes other players do not read
+    fn to_pls(&self) -> String {
+        let key_file = KeyFile::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let number = index + 1;
+            key_file.set_value(
+                "playlist",
+                &format!("File{}", number),
+                &local_path(&entry.uri),
+            );
+            if let Some(title) = &entry.title {
+                key_file.set_value(
+                    "playlist",
+                    &format!("Title{}", number),
+                    &title.replace(['\r', '\n'], " "),
+                );
+            }
+            let seconds = entry
+                .duration
+                .map_or(-1, |duration| duration.seconds() as i64);
+            key_file.set_int64("playlist", &format!("Length{}", number), seconds);
+        }
+        key_file.set_integer("playlist", "NumberOfEntries", self.entries.len() as i32);
+        key_file.set_integer("playlist", "Version", 2);
+        key_file.to_data().to_string()
+    }
+
+    fn to_xspf(&self) -> String {
+        let mut xspf = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n",
+        );
+        for entry in &self.entries {
+            xspf.push_str("    <track>\n");
+            xspf.push_str(&format!(
+                "      <location>{}</location>\n",
+                glib::markup_escape_text(&entry.uri)
+            ));
+            if let Some(title) = &entry.title {
+                xspf.push_str(&format!(
+                    "      <title>{}</title>\n",
+                    glib::markup_escape_text(title)
+                ));
+            }
+            if let Some(duration) = entry.duration {
+                xspf.push_str(&format!(
+                    "      <duration>{}</duration>\n",
+                    duration.mseconds()
+                ));
+            }
+            xspf.push_str("    </track>\n");
+        }
+        xspf.push_str("  </trackList>\n</playlist>\n");
+        xspf
+    }
+}
+
+/// URI of a playlist entry: URIs are kept, paths are resolved against `base`
+fn resolve(location: &str, base: &gio::File) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    // Playlists written on Windows separate the directories with backslashes, elsewhere
+    // they are part of the file names
+    let path = if is_windows_path(location) {
+        location.replace('\\', "/")
+    } else {
+        location.to_string()
+    };
+    base.resolve_relative_path(path).uri().to_string()
+}
+
+/// Is `location` a Windows path, starting with a drive letter or `\\` for a network share?
+fn is_windows_path(location: &str) -> bool {
+    let bytes = location.as_bytes();
+    location.starts_with("\\\\")
+        || (bytes.len() > 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && matches!(bytes[2], b'\\' | b'/'))
+}
+
+/// URI of a XSPF location, which is an URI reference: relative ones are escaped paths
+fn resolve_reference(location: &str, base: &gio::File) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let path = glib::Uri::unescape_string(location, None::<&str>)
+        .map_or_else(|| location.to_string(), |path| path.to_string());
+    resolve(&path, base)
+}
+
+/// Local files as paths, other media as URIs
+fn local_path(uri: &str) -> String {
+    glib::filename_from_uri(uri)
+        .map_or_else(|_| uri.to_string(), |(path, _)| path.display().to_string())
+}
+
+/// Duration of `#EXTINF` and PLS entries, `-1` when unknown
+fn parse_seconds(value: &str) -> Option<ClockTime> {
+    let seconds = value.trim().parse::<f64>().ok()?;
+    (seconds >= 0.0).then(|| ClockTime::from_seconds_f64(seconds))
+}
+
+fn parse_m3u(contents: &str, base: &gio::File) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    // `#EXTINF:<seconds>,<title>` describes the entry on the next line
+    let mut info: Option<(Option<ClockTime>, Option<String>)> = None;
+    for line in contents.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let (duration, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+            // Attributes such as `tvg-id="..."` may follow the duration
+            let duration = duration.split_whitespace().next().unwrap_or_default();
+            let title = Some(title.trim()).filter(|title| !title.is_empty());
+            info = Some((parse_seconds(duration), title.map(str::to_string)));
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let (duration, title) = info.take().unwrap_or_default();
+            entries.push(PlaylistEntry {
+                uri: resolve(line, base),
+                title,
+                duration,
+            });
+        }
+    }
+    entries
+}
+
+fn parse_pls(contents: &str, base: &gio::File) -> Vec<PlaylistEntry> {
+    let key_file = KeyFile::new();
+    if key_file
+        .load_from_data(contents, KeyFileFlags::NONE)
+        .is_err()
+    {
+        return Vec::new();
+    }
+    // The group is named `playlist`, in any case
+    let Some(group) = key_file
+        .groups()
+        .iter()
+        .find(|group| group.as_str().eq_ignore_ascii_case("playlist"))
+        .map(|group| group.to_string())
+    else {
+        return Vec::new();
+    };
+
+    // Entries are numbered from 1, `NumberOfEntries` is not always right. The values are
+    // read raw: backslashes are directory separators, not key file escapes.
+    let mut entries = Vec::new();
+    for number in 1.. {
+        let Ok(location) = key_file.value(&group, &format!("File{}", number)) else {
+            break;
+        };
+        entries.push(PlaylistEntry {
+            uri: resolve(location.trim(), base),
+            title: key_file
+                .value(&group, &format!("Title{}", number))
+                .ok()
+                .map(|title| title.trim().to_string())
+                .filter(|title| !title.is_empty()),
+            duration: key_file
+                .value(&group, &format!("Length{}", number))
+                .ok()
+                .and_then(|length| parse_seconds(&length)),
+        });
+    }
+    entries
+}
+
+fn parse_xspf(contents: &str, base: &gio::File) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut rest = contents;
+    while let Some((track, after)) = element(rest, "track") {
+        rest = after;
+        // A track may list several locations of the same media, the first one is used
+        let Some((location, _)) = element(track, "location") else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            uri: resolve_reference(&unescape_xml(location.trim()), base),
+            title: element(track, "title").map(|(title, _)| unescape_xml(title.trim())),
+            duration: element(track, "duration")
+                .and_then(|(duration, _)| duration.trim().parse::<u64>().ok())
+                .map(ClockTime::from_mseconds),
+        });
+    }
+    entries
+}
+
+/// Content of the first `<name>` element of `xml`, and the text after it
+fn element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut start = 0;
+    loop {
+        let tag = start + xml[start..].find(&open)?;
+        let after_name = &xml[tag + open.len()..];
+        // Skip the elements whose name only starts with `name`, e.g. `<trackList>`
+        if after_name.starts_with(['>', ' ', '\t', '\r', '\n']) {
+            let content_start = tag + open.len() + after_name.find('>')? + 1;
+            let content_end = content_start + xml[content_start..].find(&close)?;
+            return Some((
+                &xml[content_start..content_end],
+                &xml[content_end + close.len()..],
+            ));
+        }
+        start = tag + open.len();
+    }
+}
+
+/// Replace the XML entities and character references of `text`
+fn unescape_xml(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ampersand) = rest.find('&') {
+        unescaped.push_str(&rest[..ampersand]);
+        rest = &rest[ampersand..];
+        let Some(semicolon) = rest.find(';') else {
+            break;
+        };
+        let character = match &rest[1..semicolon] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            reference => reference
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| reference.strip_prefix('#').map(str::parse::<u32>))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[semicolon + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> gio::File {
+        gio::File::for_path("/music")
+    }
+
+    fn playlist() -> Playlist {
+        Playlist {
+            entries: vec![
+                PlaylistEntry {
+                    title: Some("Artist, Song & <Remix>".to_string()),
+                    duration: Some(ClockTime::from_seconds(215)),
+                    ..PlaylistEntry::new("file:///music/My%20Album/01%20Song.flac")
+                },
+                PlaylistEntry::new("http://example.com/live?channel=1&quality=hd"),
+                PlaylistEntry {
+                    title: Some("C:\\Temp\\new".to_string()),
+                    duration: None,
+                    ..PlaylistEntry::new("file:///music/clip.mkv")
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for format in [
+            PlaylistFormat::M3u,
+            PlaylistFormat::M3u8,
+            PlaylistFormat::Pls,
+            PlaylistFormat::Xspf,
+        ] {
+            let written = playlist().serialize(format);
+            assert_eq!(
+                Playlist::parse(&written, format, &base()),
+                playlist(),
+                "{format:?}:\n{written}"
+            );
+        }
+    }
+
+    #[test]
+    fn m3u_relative_paths() {
+        let m3u = "#EXTM3U\n#EXTINF:-1 tvg-id=\"a\",Intro\nMy Album/01 Intro.mp3\nAC\\DC.mp3\n";
+        let playlist = Playlist::parse(m3u, PlaylistFormat::M3u, &base());
+        assert_eq!(
+            playlist.entries,
+            [
+                PlaylistEntry {
+                    title: Some("Intro".to_string()),
+                    ..PlaylistEntry::new("file:///music/My%20Album/01%20Intro.mp3")
+                },
+                PlaylistEntry::new("file:///music/AC%5CDC.mp3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pls_relative_paths() {
+        let pls = "[Playlist]\nFile1=new\\track.mp3\nTitle1=First\nLength1=12\nFile2=/tmp/a b.ogg\nNumberOfEntries=2\n";
+        let playlist = Playlist::parse(pls, PlaylistFormat::Pls, &base());
+        assert_eq!(
+            playlist.entries,
+            [
+                PlaylistEntry {
+                    title: Some("First".to_string()),
+                    duration: Some(ClockTime::from_seconds(12)),
+                    ..PlaylistEntry::new("file:///music/new%5Ctrack.mp3")
+                },
+                PlaylistEntry::new("file:///tmp/a%20b.ogg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        for location in ["C:\\Music\\a.mp3", "c:/a.mp3", "\\\\server\\share\\a.mp3"] {
+            assert!(is_windows_path(location), "{}", location);
+        }
+        for location in ["AC\\DC.mp3", "new\\track.mp3", "a:b.mp3", "C:", "\\a.mp3"] {
+            assert!(!is_windows_path(location), "{}", location);
+        }
+    }
+
+    #[test]
+    fn xspf_relative_locations() {
+        let xspf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList>
+    <track><location>My%20Album/%C3%A9t%C3%A9.ogg</location></track>
+    <track><location>http://example.com/live?channel=1&amp;quality=hd</location></track>
+    <track>
+      <location>file:///tmp/clip.mkv</location>
+      <title>Clip</title>
+    </track>
+  </trackList>
+</playlist>"#;
+        let playlist = Playlist::parse(xspf, PlaylistFormat::Xspf, &base());
+        assert_eq!(
+            playlist.entries,
+            [
+                PlaylistEntry::new("file:///music/My%20Album/%C3%A9t%C3%A9.ogg"),
+                PlaylistEntry::new("http://example.com/live?channel=1&quality=hd"),
+                PlaylistEntry {
+                    title: Some("Clip".to_string()),
+                    ..PlaylistEntry::new("file:///tmp/clip.mkv")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hls_manifests_are_played() {
+        let directory = std::env::temp_dir();
+        let manifest = directory.join(format!("manifest-{}.m3u8", std::process::id()));
+        let list = directory.join(format!("list-{}.m3u8", std::process::id()));
+        std::fs::write(
+            &manifest,
+            "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\nsegment0.ts\n",
+        )
+        .unwrap();
+        std::fs::write(&list, "#EXTM3U\n#EXTINF:10,Intro\nintro.mp3\n").unwrap();
+        let load = |path: &Path| Playlist::load(&glib::filename_to_uri(path, None).unwrap());
+        let (manifest_playlist, list_playlist) = (load(&manifest), load(&list));
+        std::fs::remove_file(&manifest).unwrap();
+        std::fs::remove_file(&list).unwrap();
+
+        assert!(matches!(manifest_playlist, Ok(None)));
+        assert!(matches!(list_playlist, Ok(Some(playlist)) if playlist.entries.len() == 1));
+        assert!(matches!(
+            Playlist::load("http://example.com/list.m3u"),
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn xml_entities() {
+        assert_eq!(
+            unescape_xml("a &amp; b &lt;c&gt; &quot;d&quot; &apos;e&apos;"),
+            "a & b <c> \"d\" 'e'"
+        );
+        assert_eq!(unescape_xml("&#233;&#xE9;&#x1F600;"), "éé😀");
+        assert_eq!(
+            unescape_xml("R&B &unknown; &#xZZ; & end"),
+            "R&B &unknown; &#xZZ; & end"
+        );
+        assert_eq!(unescape_xml("no entity"), "no entity");
+    }
+}
@@ -0,0 +1,349 @@
+use {
+    crate::{encoding::EncodingFormat, media_player::MediaPlayerErrors, transcoder::discard_pad},
+    gstreamer::{prelude::*, *},
+    std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::Instant,
+    },
+};
+
+/// Name of the application message posted when a recording is finished or failed
+pub const RECORDING_ENDED: &str = "aug-recording-ended";
+
+/// How and where the recordings are written
+#[derive(Debug, Clone)]
+pub struct RecordingSettings {
+    pub format: EncodingFormat,
+    pub directory: PathBuf,
+    /// File name, without extension. `{title}` is replaced by the name of the media
+    /// and `%` sequences are `strftime` formats, e.g. `{title}-%Y%m%d-%H%M%S`
+    pub template: String,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            format: EncodingFormat::Mkv,
+            directory: glib::user_special_dir(glib::UserDirectory::Videos)
+                .unwrap_or_else(|| PathBuf::from(".")),
+            template: "{title}-%Y%m%d-%H%M%S".to_string(),
+        }
+    }
+}
+
+impl RecordingSettings {
+    pub fn output_path(&self, uri: &str) -> PathBuf {
+        let name = self.template.replace("{title}", &media_title(uri));
+        let name = glib::DateTime::now_local()
+            .and_then(|now| now.format(&name))
+            .map_or(name, |formatted| formatted.to_string());
+        self.directory
+            .join(format!("{}.{}", name, self.format.extension()))
+    }
+}
+
+/// Name of a media for file names: the last segment of its URI, without extension
+pub fn media_title(uri: &str) -> String {
+    let segment = uri
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(uri)
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let title = segment.rsplit_once('.').map_or(segment, |(title, _)| title);
+    if title.is_empty() {
+        "recording".to_string()
+    } else {
+        title.to_string()
+    }
+}
+
+/// Records the media being played to a file. The streams flowing into the decoders of
+/// the player, or into its filters for raw streams, are copied to a pipeline of its own, so
+/// the recording matches the playback and no second connection is opened.
+/// `appsrc ! [decodebin !] encodebin ! filesink`
+pub struct Recorder {
+    pipeline: Pipeline,
+    /// Pads of the player copied to the recording, with their probes
+    taps: Vec<(Pad, PadProbeId)>,
+    /// `appsrc` of each tapped stream
+    sources: Vec<Element>,
+    path: PathBuf,
+    started_at: Instant,
+    /// Has the end of stream been sent to finish the file?
+    stopping: bool,
+}
+
+/// Mapping of the running time of the player to the time of the recording, shared by the
+/// streams so they stay in sync
+#[derive(Default)]
+struct Timeline {
+    /// Running time of the player where the recording starts
+    base: Option<ClockTime>,
+    /// Latest time written, where the recording goes on after a seek
+    last: ClockTime,
+    /// Sequence number of the last flushing seek
+    flush: Option<Seqnum>,
+    /// Is the next buffer the first one after a seek?
+    resync: bool,
+}
+
+impl Timeline {
+    /// Time in the recording of a buffer played at `running_time`, `None` before the start
+    fn map(&mut self, running_time: ClockTime) -> Option<ClockTime> {
+        if self.resync {
+            self.resync = false;
+            self.base = Some(running_time.saturating_sub(self.last));
+        }
+        let time = running_time.checked_sub(*self.base.get_or_insert(running_time))?;
+        self.last = self.last.max(time);
+        Some(time)
+    }
+}
+
+impl Recorder {
+    /// Start recording the streams flowing through `taps`, pads of the player carrying
+    /// parsed or raw streams. `uri` names the file. The end of the recording is posted on
+    /// `player_bus` as an application message named [`RECORDING_ENDED`], with an `eos`
+    /// field once the file is complete or an `error` field on failure.
+    pub fn start(
+        uri: &str,
+        taps: &[Pad],
+        settings: &RecordingSettings,
+        player_bus: Bus,
+    ) -> Result<Self, MediaPlayerErrors> {
+        let path = settings.output_path(uri);
+        let pipeline = Pipeline::with_name("recorder");
+
+        let encodebin = ElementFactory::make("encodebin")
+            .property("profile", settings.format.profile())
+            .build()
+            .map_err(MediaPlayerErrors::ErrorRecording)?;
+        let filesink = ElementFactory::make("filesink")
+            .property("location", &*path.to_string_lossy())
+            .build()
+            .map_err(MediaPlayerErrors::ErrorRecording)?;
+        pipeline
+            .add_many([&encodebin, &filesink])
+            .map_err(MediaPlayerErrors::ErrorRecording)?;
+        encodebin
+            .link(&filesink)
+            .map_err(MediaPlayerErrors::ErrorRecording)?;
+
+        let mut streams = Vec::new();
+        for tap in taps {
+            let Some(caps) = tap.current_caps() else {
+                continue;
+            };
+            // Buffers are timestamped by the tap, in the running time of the recording
+            let source = ElementFactory::make("appsrc")
+                .property("caps", &caps)
+                .property("is-live", true)
+                .property_from_str("format", "time")
+                .build()
+                .map_err(MediaPlayerErrors::ErrorRecording)?;
+            pipeline
+                .add(&source)
+                .map_err(MediaPlayerErrors::ErrorRecording)?;
+            if let Some(pad) = source.static_pad("src") {
+                link_to_encoder(&pipeline, &encodebin, &pad);
+            }
+            streams.push((tap, source));
+        }
+        if streams.is_empty() {
+            return Err(MediaPlayerErrors::ErrorNothingToRecord);
+        }
+
+        pipeline.bus().unwrap().set_sync_handler(move |_, msg| {
+            let structure = match msg.view() {
+                MessageView::Eos(_) => Structure::builder(RECORDING_ENDED).field("eos", true),
+                MessageView::Error(err) => {
+                    Structure::builder(RECORDING_ENDED).field("error", err.error().to_string())
+                }
+                _ => return BusSyncReply::Drop,
+            }
+            .build();
+            player_bus.post(message::Application::new(structure)).ok();
+            BusSyncReply::Drop
+        });
+
+        pipeline
+            .set_state(State::Playing)
+            .map_err(MediaPlayerErrors::ErrorStartingRecording)?;
+
+        // The probes only copy the buffers, the playback never waits for the recording
+        let timeline = Arc::new(Mutex::new(Timeline::default()));
+        let taps = streams
+            .iter()
+            .filter_map(|(tap, source)| {
+                let probe = tap_stream(tap, source, timeline.clone())?;
+                Some(((*tap).clone(), probe))
+            })
+            .collect();
+        let sources = streams.into_iter().map(|(_, source)| source).collect();
+
+        Ok(Self {
+            pipeline,
+            taps,
+            sources,
+            path,
+            started_at: Instant::now(),
+            stopping: false,
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn stopping(&self) -> bool {
+        self.stopping
+    }
+
+    /// Stop copying the streams and finish the file. [`RECORDING_ENDED`] is posted once it
+    /// is written.
+    pub fn stop(&mut self) {
+        self.stopping = true;
+        self.remove_taps();
+        for source in &self.sources {
+            source.emit_by_name::<FlowReturn>("end-of-stream", &[]);
+        }
+    }
+
+    fn remove_taps(&mut self) {
+        for (pad, probe) in self.taps.drain(..) {
+            pad.remove_probe(probe);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.remove_taps();
+        self.pipeline.set_state(State::Null).ok();
+    }
+}
+
+/// Copy the buffers flowing through `tap` to `source`, with their timestamps mapped by
+/// `timeline`. Video streams start at a keyframe, so they can be remuxed.
+fn tap_stream(tap: &Pad, source: &Element, timeline: Arc<Mutex<Timeline>>) -> Option<PadProbeId> {
+    let is_video = tap.current_caps().is_some_and(|caps| {
+        caps.structure(0)
+            .is_some_and(|structure| structure.name().starts_with("video/"))
+    });
+    let segment = tap
+        .sticky_event::<event::Segment>(0)
+        .and_then(|event| event.segment().downcast_ref::<ClockTime>().cloned());
+    // Segment of the stream, and is it waiting for a keyframe?
+    let state = Mutex::new((segment, is_video));
+    let source = source.clone();
+
+    tap.add_probe(
+        PadProbeType::BUFFER | PadProbeType::EVENT_DOWNSTREAM,
+        move |_, info| {
+            let mut state = state.lock().unwrap();
+            let (segment, waiting_keyframe) = &mut *state;
+            if let Some(event) = info.event() {
+                match event.view() {
+                    EventView::Segment(event) => {
+                        *segment = event.segment().downcast_ref::<ClockTime>().cloned();
+                    }
+                    EventView::Caps(event) => source.set_property("caps", event.caps_owned()),
+                    EventView::FlushStop(_) => {
+                        // The running time of the player starts again after a seek
+                        let mut timeline = timeline.lock().unwrap();
+                        if timeline.flush != Some(event.seqnum()) {
+                            timeline.flush = Some(event.seqnum());
+                            timeline.resync = true;
+                        }
+                        *waiting_keyframe = is_video;
+                    }
+                    EventView::Eos(_) => {
+                        source.emit_by_name::<FlowReturn>("end-of-stream", &[]);
+                    }
+                    _ => (),
+                }
+                return PadProbeReturn::Ok;
+            }
+
+            let (Some(buffer), Some(segment)) = (info.buffer(), segment.as_ref()) else {
+                return PadProbeReturn::Ok;
+            };
+            if *waiting_keyframe && buffer.flags().contains(BufferFlags::DELTA_UNIT) {
+                return PadProbeReturn::Ok;
+            }
+            let Some(running_time) = buffer.pts().and_then(|pts| segment.to_running_time(pts))
+            else {
+                return PadProbeReturn::Ok;
+            };
+            let (time, base) = {
+                let mut timeline = timeline.lock().unwrap();
+                let Some(time) = timeline.map(running_time) else {
+                    return PadProbeReturn::Ok;
+                };
+                (time, timeline.base.unwrap_or_default())
+            };
+            *waiting_keyframe = false;
+
+            // The copy shares the memory of the buffer
+            let mut copy = buffer.copy();
+            {
+                let copy = copy.make_mut();
+                copy.set_pts(time);
+                copy.set_dts(
+                    buffer
+                        .dts()
+                        .and_then(|dts| segment.to_running_time(dts))
+                        .and_then(|dts| dts.checked_sub(base)),
+                );
+            }
+            source.emit_by_name::<FlowReturn>("push-buffer", &[&copy]);
+            PadProbeReturn::Ok
+        },
+    )
+}
+
+/// Link a recorded stream to `encodebin`. Streams already in the profile formats are muxed
+/// as they are, the others are decoded first and encoded by `encodebin`.
+fn link_to_encoder(pipeline: &Pipeline, encodebin: &Element, pad: &Pad) {
+    let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+    if let Some(sink) = encodebin.emit_by_name::<Option<Pad>>("request-pad", &[&caps])
+        && pad.link(&sink).is_ok()
+    {
+        return;
+    }
+
+    let Ok(decodebin) = ElementFactory::make("decodebin").build() else {
+        return;
+    };
+    if pipeline.add(&decodebin).is_err() {
+        return;
+    }
+    decodebin.sync_state_with_parent().ok();
+    if let Some(sink) = decodebin.static_pad("sink") {
+        pad.link(&sink).ok();
+    }
+    let pipeline_weak = pipeline.downgrade();
+    let encodebin = encodebin.downgrade();
+    decodebin.connect_pad_added(move |_, pad| {
+        let (Some(pipeline), Some(encodebin)) = (pipeline_weak.upgrade(), encodebin.upgrade())
+        else {
+            return;
+        };
+        let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+        match encodebin.emit_by_name::<Option<Pad>>("request-pad", &[&caps]) {
+            Some(sink) => {
+                pad.link(&sink).ok();
+            }
+            // e.g. the video of audio-only formats
+            None => discard_pad(&pipeline, pad),
+        }
+    });
+}
@@ -0,0 +1,279 @@
+use {
+    crate::{
+        encoding::{self, EncodingFormat},
+        media_player::MediaPlayerErrors,
+    },
+    gstreamer::{bus::BusWatchGuard, prelude::*, *},
+    std::{
+        cell::Cell,
+        path::{Path, PathBuf},
+        rc::Rc,
+        time::Duration,
+    },
+};
+
+/// Interval of the progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Progress of a transcoding, reported to the callback of [`Transcoder::start`]
+#[derive(Debug, Clone)]
+pub enum TranscodeEvent {
+    /// Fraction of the output written, from 0 to 1
+    Progress(f64),
+    /// The output is complete
+    Finished,
+    /// The transcoding failed, the partial file is left as it is
+    Failed(String),
+}
+
+/// What the output of a [`Transcoder`] looks like
+#[derive(Debug, Clone)]
+pub struct TranscodeOptions {
+    pub format: EncodingFormat,
+    /// Width of the video, following the aspect ratio when only the height is set
+    pub width: Option<u32>,
+    /// Height of the video, following the aspect ratio when only the width is set
+    pub height: Option<u32>,
+    /// Bitrate of the video encoder, in kbps
+    pub video_bitrate: Option<u32>,
+    /// Bitrate of the audio encoder, in kbps
+    pub audio_bitrate: Option<u32>,
+    /// Segment of the media to transcode, the whole media when `None`
+    pub range: Option<(ClockTime, ClockTime)>,
+}
+
+impl TranscodeOptions {
+    pub fn new(format: EncodingFormat) -> Self {
+        Self {
+            format,
+            width: None,
+            height: None,
+            video_bitrate: None,
+            audio_bitrate: None,
+            range: None,
+        }
+    }
+}
+
+/// Converts a media to a new file with the profile of an [`EncodingFormat`]. It runs its
+/// own pipeline, so it can be used next to the playback or without any window.
+/// `uridecodebin ! encodebin ! filesink`
+pub struct Transcoder {
+    pipeline: Pipeline,
+    output: PathBuf,
+    /// Has the transcoding ended, successfully or not?
+    ended: Rc<Cell<bool>>,
+    _bus_watch: BusWatchGuard,
+}
+
+impl Transcoder {
+    /// Start transcoding `uri` into `output`. The progress is reported to `callback` from
+    /// the main loop until [`TranscodeEvent::Finished`] or [`TranscodeEvent::Failed`].
+    pub fn start(
+        uri: &str,
+        output: &Path,
+        options: &TranscodeOptions,
+        callback: impl Fn(TranscodeEvent) + 'static,
+    ) -> Result<Self, MediaPlayerErrors> {
+        if let Some((from, to)) = options.range
+            && to <= from
+        {
+            return Err(MediaPlayerErrors::ErrorInvalidClip);
+        }
+
+        let pipeline = Pipeline::with_name("transcoder");
+        let source = ElementFactory::make("uridecodebin")
+            .property("uri", uri)
+            .build()
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+        let encodebin = ElementFactory::make("encodebin")
+            .property(
+                "profile",
+                options.format.scaled_profile(options.width, options.height),
+            )
+            .build()
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+        let filesink = ElementFactory::make("filesink")
+            .property("location", &*output.to_string_lossy())
+            .build()
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+
+        pipeline
+            .add_many([&source, &encodebin, &filesink])
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+        encodebin
+            .link(&filesink)
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+
+        // The encoders are only created once the streams are linked
+        let (video_bitrate, audio_bitrate) = (options.video_bitrate, options.audio_bitrate);
+        if let Some(encodebin) = encodebin.dynamic_cast_ref::<Bin>() {
+            encodebin.connect_deep_element_added(move |_, _, element| {
+                let Some(klass) = element
+                    .factory()
+                    .and_then(|factory| factory.metadata("klass").map(|klass| klass.to_string()))
+                else {
+                    return;
+                };
+                let bitrate = if klass.contains("Encoder/Video") {
+                    video_bitrate
+                } else if klass.contains("Encoder/Audio") {
+                    audio_bitrate
+                } else {
+                    None
+                };
+                if let Some(kbps) = bitrate {
+                    encoding::set_encoder_bitrate(element, kbps);
+                }
+            });
+        }
+
+        let pipeline_weak = pipeline.downgrade();
+        let encodebin_weak = encodebin.downgrade();
+        source.connect_pad_added(move |_, pad| {
+            if let (Some(pipeline), Some(encodebin)) =
+                (pipeline_weak.upgrade(), encodebin_weak.upgrade())
+            {
+                link_decoded_pad(&pipeline, &encodebin, pad);
+            }
+        });
+
+        let callback = Rc::new(callback);
+        let ended = Rc::new(Cell::new(false));
+
+        // A segment can only be selected once the pipeline is prerolled
+        let range = options.range;
+        let prerolled = Cell::new(false);
+        let pipeline_weak = pipeline.downgrade();
+        let bus_watch = pipeline
+            .bus()
+            .unwrap()
+            .add_watch_local({
+                let callback = callback.clone();
+                let ended = ended.clone();
+                move |_, msg| {
+                    let Some(pipeline) = pipeline_weak.upgrade() else {
+                        return glib::ControlFlow::Continue;
+                    };
+                    if ended.get() {
+                        return glib::ControlFlow::Continue;
+                    }
+                    let failure = match msg.view() {
+                        MessageView::AsyncDone(_) if !prerolled.get() => {
+                            prerolled.set(true);
+                            let seek = range.map_or(Ok(()), |(from, to)| {
+                                pipeline.seek(
+                                    1.0,
+                                    SeekFlags::FLUSH | SeekFlags::ACCURATE,
+                                    SeekType::Set,
+                                    from,
+                                    SeekType::Set,
+                                    to,
+                                )
+                            });
+                            match seek {
+                                Ok(()) => {
+                                    pipeline.set_state(State::Playing).ok();
+                                    return glib::ControlFlow::Continue;
+                                }
+                                Err(err) => Some(err.to_string()),
+                            }
+                        }
+                        MessageView::Eos(_) => None,
+                        MessageView::Error(err) => Some(err.error().to_string()),
+                        _ => return glib::ControlFlow::Continue,
+                    };
+                    ended.set(true);
+                    pipeline.set_state(State::Null).ok();
+                    callback(match failure {
+                        Some(err) => TranscodeEvent::Failed(err),
+                        None => TranscodeEvent::Finished,
+                    });
+                    glib::ControlFlow::Continue
+                }
+            })
+            .map_err(MediaPlayerErrors::ErrorTranscoding)?;
+
+        let pipeline_weak = pipeline.downgrade();
+        let progress_ended = ended.clone();
+        glib::timeout_add_local(PROGRESS_INTERVAL, move || {
+            let Some(pipeline) = pipeline_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if progress_ended.get() {
+                return glib::ControlFlow::Break;
+            }
+            // Positions are in the time of the media, so segments start at `from`
+            let (from, to) = match range {
+                Some(range) => range,
+                None => match pipeline.query_duration::<ClockTime>() {
+                    Some(duration) if duration > ClockTime::ZERO => (ClockTime::ZERO, duration),
+                    _ => return glib::ControlFlow::Continue,
+                },
+            };
+            if let Some(position) = pipeline.query_position::<ClockTime>() {
+                let written = position.saturating_sub(from).nseconds() as f64;
+                let progress = written / (to - from).nseconds() as f64;
+                callback(TranscodeEvent::Progress(progress.clamp(0.0, 1.0)));
+            }
+            glib::ControlFlow::Continue
+        });
+
+        pipeline
+            .set_state(State::Paused)
+            .map_err(MediaPlayerErrors::ErrorStartingTranscoding)?;
+
+        Ok(Self {
+            pipeline,
+            output: output.to_path_buf(),
+            ended,
+            _bus_watch: bus_watch,
+        })
+    }
+
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    /// Stop the transcoding and remove the partial file. No event is reported afterwards.
+    pub fn cancel(self) {
+        let finished = self.ended.replace(true);
+        self.pipeline.set_state(State::Null).ok();
+        if !finished {
+            std::fs::remove_file(&self.output).ok();
+        }
+    }
+}
+
+impl Drop for Transcoder {
+    fn drop(&mut self) {
+        self.pipeline.set_state(State::Null).ok();
+    }
+}
+
+/// Link a decoded stream to `encodebin`. Streams the profile has no room for, such as
+/// subtitles or the video of audio-only formats, are discarded.
+fn link_decoded_pad(pipeline: &Pipeline, encodebin: &Element, pad: &Pad) {
+    let caps = pad.current_caps().unwrap_or_else(|| pad.query_caps(None));
+    if let Some(sink) = encodebin.emit_by_name::<Option<Pad>>("request-pad", &[&caps]) {
+        pad.link(&sink).ok();
+    } else {
+        discard_pad(pipeline, pad);
+    }
+}
+
+/// Link `pad` to a `fakesink`, so an unused stream does not stop the pipeline
+pub(crate) fn discard_pad(pipeline: &Pipeline, pad: &Pad) {
+    let Ok(fakesink) = ElementFactory::make("fakesink")
+        .property("sync", false)
+        .build()
+    else {
+        return;
+    };
+    if pipeline.add(&fakesink).is_ok() {
+        fakesink.sync_state_with_parent().ok();
+        if let Some(sink) = fakesink.static_pad("sink") {
+            pad.link(&sink).ok();
+        }
+    }
+}
@@ -1,81 +1,1901 @@
 use {
-    crate::media_player::MediaPlayerRef,
+    crate::{
+        adaptive::{QualityLock, Variant},
+        bookmarks::Bookmark,
+        config::OriginType,
+        devices,
+        encoding::EncodingFormat,
+        library::{Library, LibraryRef},
+        media_player::{MediaPlayer, MediaPlayerRef, PlayerEvent, short_time},
+        playlist::Playlist,
+        recorder::media_title,
+        transcoder::{TranscodeEvent, TranscodeOptions, Transcoder},
+        video_filter::{Deinterlace, DeinterlaceMethod, VideoBalance},
+        video_transform::{self, ASPECT_PRESETS, VideoTransform},
+        visualization::Visualization,
+    },
+    clap::ValueEnum,
     gtk::{
-        Application, ApplicationWindow, Button, Dialog, Label, ResponseType, Scale,
+        Application, ApplicationWindow, Button, Dialog, Label, ResponseType, Scale, gio,
         glib::{self, clone},
         prelude::*,
     },
+    std::{cell::RefCell, path::PathBuf, rc::Rc, time::Duration},
 };
 
-pub fn refresh_ui(window: &ApplicationWindow, media_player: &MediaPlayerRef, duration_bar: &Scale) {
-    duration_bar.connect_change_value(clone!(
+/// How long an on-screen message stays over the video
+const OSD_DURATION: Duration = Duration::from_millis(1500);
+/// Step of the audio/video offset shortcuts, in milliseconds
+const AV_OFFSET_STEP: i64 = 50;
+
+pub fn refresh_ui(window: &ApplicationWindow, media_player: &MediaPlayerRef, duration_bar: &Scale) {
+    duration_bar.connect_change_value(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        #[upgrade_or]
+        glib::Propagation::Stop,
+        move |duration_b, _, new_pos| {
+            let mut media_player_ref = media_player.borrow_mut();
+            media_player_ref.set_user_is_seeking(true);
+            if media_player_ref.pause_player().is_err() {
+                error_dialog(&window, "There was a problem seeking the media.");
+                media_player_ref.set_user_is_seeking(false);
+                return glib::Propagation::Stop;
+            }
+            if let Some(duration) = media_player_ref.duration() {
+                let seek_pos = (new_pos / 100_f64) * duration.seconds_f64();
+                if let Err(err) =
+                    media_player_ref.seek_position(gstreamer::ClockTime::from_seconds_f64(seek_pos))
+                {
+                    error_dialog(&window, &format!("{}", err));
+                    media_player_ref.set_user_is_seeking(false);
+                    return glib::Propagation::Stop;
+                }
+                duration_b.set_value(new_pos);
+            }
+
+            if media_player_ref.play_player().is_err() {
+                error_dialog(&window, "There was a problem seeking the media.");
+                media_player_ref.set_user_is_seeking(false);
+                return glib::Propagation::Stop;
+            }
+
+            // Placeholder for future functionality
+            media_player_ref.set_user_is_seeking(false);
+
+            glib::Propagation::Stop
+        }
+    ));
+
+    glib::timeout_add_seconds_local(
+        1,
+        clone!(
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            #[upgrade_or]
+            glib::ControlFlow::Break,
+            move || {
+                let media_player_ref = media_player.borrow();
+                if !media_player_ref.seek_enabled() {
+                    duration_bar.set_value(100.0);
+                    return glib::ControlFlow::Continue;
+                }
+                if media_player_ref.playing() && !media_player_ref.user_is_seeking() {
+                    if let (Ok(position), Some(duration)) =
+                        (media_player_ref.get_position(), media_player_ref.duration())
+                    {
+                        let bar_position =
+                            (100_f64 / duration.seconds_f64()) * position.seconds_f64();
+                        duration_bar.set_value(bar_position);
+                    }
+                }
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+}
+
+/// Redraw the marks of the duration bar: chapter ticks, bookmarks and the A-B loop points
+pub fn update_duration_marks(duration_bar: &Scale, media_player: &MediaPlayer) {
+    duration_bar.clear_marks();
+    let Some(duration) = media_player.duration() else {
+        return;
+    };
+    let bar_position = |position: gstreamer::ClockTime| {
+        (100_f64 / duration.seconds_f64()) * position.seconds_f64()
+    };
+
+    for chapter in media_player.chapters() {
+        duration_bar.add_mark(bar_position(chapter.start), gtk::PositionType::Top, None);
+    }
+    for bookmark in media_player.bookmarks() {
+        duration_bar.add_mark(
+            bar_position(bookmark.position),
+            gtk::PositionType::Top,
+            Some("•"),
+        );
+    }
+    if let Some(start) = media_player.loop_start() {
+        duration_bar.add_mark(bar_position(start), gtk::PositionType::Bottom, Some("A"));
+    }
+    if let Some(end) = media_player.loop_end() {
+        duration_bar.add_mark(bar_position(end), gtk::PositionType::Bottom, Some("B"));
+    }
+    if let Some(clip_in) = media_player.clip_in() {
+        duration_bar.add_mark(bar_position(clip_in), gtk::PositionType::Bottom, Some("["));
+    }
+    if let Some(clip_out) = media_player.clip_out() {
+        duration_bar.add_mark(bar_position(clip_out), gtk::PositionType::Bottom, Some("]"));
+    }
+}
+
+/// Register a window action, reachable from menus as `win.<name>`, with its keyboard shortcuts
+fn add_window_action(
+    window: &ApplicationWindow,
+    name: &str,
+    accels: &[&str],
+    callback: impl Fn() + 'static,
+) {
+    let action = gio::SimpleAction::new(name, None);
+    action.connect_activate(move |_, _| callback());
+    window.add_action(&action);
+    if let Some(app) = window.application() {
+        app.set_accels_for_action(&format!("win.{}", name), accels);
+    }
+}
+
+/// Register a window action taking a string parameter, targeted from menus items
+fn add_window_string_action(
+    window: &ApplicationWindow,
+    name: &str,
+    callback: impl Fn(&str) + 'static,
+) {
+    let action = gio::SimpleAction::new(name, Some(glib::VariantTy::STRING));
+    action.connect_activate(move |_, parameter| {
+        if let Some(value) = parameter.and_then(|parameter| parameter.str()) {
+            callback(value);
+        }
+    });
+    window.add_action(&action);
+}
+
+/// Menu item activating `action` with a string target
+fn menu_item(label: &str, action: &str, target: &str) -> gio::MenuItem {
+    let item = gio::MenuItem::new(Some(label), None);
+    item.set_action_and_target_value(Some(action), Some(&target.to_variant()));
+    item
+}
+
+/// Menu of the header bar, rebuilt every time it opens so the device lists stay current
+fn build_main_menu(media_player: &MediaPlayer) -> gio::Menu {
+    let menu = gio::Menu::new();
+
+    menu.append(Some("Open File…"), Some("win.open-file"));
+    menu.append(Some("Library"), Some("win.library"));
+    let recent_menu = gio::Menu::new();
+    let recent_section = gio::Menu::new();
+    for recent in media_player.recent_media() {
+        recent_section.append_item(&menu_item(&recent.title, "win.open-recent", &recent.uri));
+    }
+    recent_menu.append_section(None, &recent_section);
+    let history_section = gio::Menu::new();
+    history_section.append(Some("Clear History"), Some("win.clear-history"));
+    history_section.append(Some("Remember Recent Media"), Some("win.history-enabled"));
+    recent_menu.append_section(None, &history_section);
+    menu.append_submenu(Some("Open Recent"), &recent_menu);
+    menu.append(Some("Save Playlist…"), Some("win.playlist-save"));
+
+    let watch_later_menu = gio::Menu::new();
+    let watch_later_section = gio::Menu::new();
+    for media in media_player.watch_later() {
+        watch_later_section.append_item(&menu_item(
+            &media.title,
+            "win.open-watch-later",
+            &media.uri,
+        ));
+    }
+    watch_later_menu.append_section(None, &watch_later_section);
+    watch_later_menu.append(Some("Watch This Later"), Some("win.watch-later"));
+    menu.append_submenu(Some("Watch Later"), &watch_later_menu);
+
+    let bookmarks_menu = gio::Menu::new();
+    bookmarks_menu.append(Some("Add Bookmark"), Some("win.bookmark-add"));
+    bookmarks_menu.append(Some("Show Bookmarks"), Some("win.bookmarks-sidebar"));
+    bookmarks_menu.append(Some("Import Bookmarks…"), Some("win.bookmarks-import"));
+    bookmarks_menu.append(Some("Export Bookmarks…"), Some("win.bookmarks-export"));
+    menu.append_submenu(Some("Bookmarks"), &bookmarks_menu);
+
+    let capture_menu = gio::Menu::new();
+    for device in devices::list_capture_devices() {
+        let name = device.display_name();
+        capture_menu.append_item(&menu_item(&name, "win.open-device", &name));
+    }
+    menu.append_submenu(Some("Capture Devices"), &capture_menu);
+
+    // The player keeps watching the outputs, so plugged ones show up here
+    let audio_menu = gio::Menu::new();
+    audio_menu.append_item(&menu_item("Default", "win.audio-device", ""));
+    for device in media_player.audio_devices() {
+        let name = device.display_name();
+        audio_menu.append_item(&menu_item(&name, "win.audio-device", &name));
+    }
+    menu.append_submenu(Some("Audio Output"), &audio_menu);
+    menu.append(Some("Reset A/V Offset"), Some("win.av-offset-reset"));
+    menu.append_submenu(Some("Video"), &build_video_menu());
+
+    let visualization_menu = gio::Menu::new();
+    for visualization in Visualization::value_variants() {
+        if let Some(value) = visualization.to_possible_value() {
+            visualization_menu.append_item(&menu_item(
+                visualization.label(),
+                "win.visualization",
+                value.get_name(),
+            ));
+        }
+    }
+    menu.append_submenu(Some("Visualization"), &visualization_menu);
+    menu.append(Some("Export Clip…"), Some("win.clip-export"));
+
+    menu
+}
+
+/// Rotation, flips, crop, zoom and aspect ratio entries of the main menu
+fn build_video_menu() -> gio::Menu {
+    let video_menu = gio::Menu::new();
+
+    let orientation_section = gio::Menu::new();
+    orientation_section.append(Some("Rotate Clockwise"), Some("win.rotate-clockwise"));
+    orientation_section.append(
+        Some("Rotate Counterclockwise"),
+        Some("win.rotate-counterclockwise"),
+    );
+    orientation_section.append(Some("Flip Horizontally"), Some("win.flip-horizontal"));
+    orientation_section.append(Some("Flip Vertically"), Some("win.flip-vertical"));
+    video_menu.append_section(None, &orientation_section);
+
+    let crop_menu = gio::Menu::new();
+    let aspect_menu = gio::Menu::new();
+    crop_menu.append_item(&menu_item("None", "win.crop", "none"));
+    aspect_menu.append_item(&menu_item("Automatic", "win.aspect-ratio", "auto"));
+    for (label, _, _) in ASPECT_PRESETS {
+        crop_menu.append_item(&menu_item(label, "win.crop", label));
+        aspect_menu.append_item(&menu_item(label, "win.aspect-ratio", label));
+    }
+    let geometry_section = gio::Menu::new();
+    geometry_section.append_submenu(Some("Crop"), &crop_menu);
+    geometry_section.append_submenu(Some("Aspect Ratio"), &aspect_menu);
+    geometry_section.append(Some("Zoom In"), Some("win.zoom-in"));
+    geometry_section.append(Some("Zoom Out"), Some("win.zoom-out"));
+    video_menu.append_section(None, &geometry_section);
+
+    let deinterlace_menu = gio::Menu::new();
+    for (label, mode) in [("Automatic", "auto"), ("On", "on"), ("Off", "off")] {
+        deinterlace_menu.append_item(&menu_item(label, "win.deinterlace", mode));
+    }
+    let method_menu = gio::Menu::new();
+    for method in DeinterlaceMethod::value_variants() {
+        if let Some(value) = method.to_possible_value() {
+            // The doc comments of the methods describe them
+            let label = value
+                .get_help()
+                .map_or_else(|| value.get_name().to_string(), |help| help.to_string());
+            method_menu.append_item(&menu_item(
+                &label,
+                "win.deinterlace-method",
+                value.get_name(),
+            ));
+        }
+    }
+    let deinterlace_section = gio::Menu::new();
+    deinterlace_section.append_submenu(Some("Deinterlace"), &deinterlace_menu);
+    deinterlace_section.append_submenu(Some("Deinterlace Method"), &method_menu);
+    video_menu.append_section(None, &deinterlace_section);
+
+    video_menu.append(Some("Reset"), Some("win.video-reset"));
+
+    video_menu
+}
+
+pub fn build_menu_button(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+) -> gtk::MenuButton {
+    add_window_string_action(
+        window,
+        "visualization",
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |value| {
+                let Ok(visualization) = Visualization::from_str(value, true) else {
+                    return;
+                };
+                let result = media_player.borrow_mut().set_visualization(visualization);
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "audio-device",
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |name| {
+                // An empty name selects the default output
+                let device = if name.is_empty() {
+                    None
+                } else {
+                    let device = media_player
+                        .borrow()
+                        .audio_devices()
+                        .into_iter()
+                        .find(|device| device.display_name() == name);
+                    if device.is_none() {
+                        error_dialog(
+                            &window,
+                            &format!("The audio output {} is no longer available", name),
+                        );
+                        return;
+                    }
+                    device
+                };
+                let result = media_player.borrow_mut().set_audio_device(device);
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "open-device",
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |name| {
+                let Some(device) = devices::find_capture_device(name) else {
+                    error_dialog(
+                        &window,
+                        &format!("The device {} is no longer available", name),
+                    );
+                    return;
+                };
+                let result = media_player.borrow_mut().open_device(&device);
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    let menu_button = gtk::MenuButton::builder()
+        .icon_name("open-menu-symbolic")
+        .build();
+    menu_button.set_create_popup_func(clone!(
+        #[weak]
+        media_player,
+        move |button| {
+            button.set_menu_model(Some(&build_main_menu(&media_player.borrow())));
+        }
+    ));
+
+    menu_button
+}
+
+/// A-B loop shortcuts: `a` sets point A, `b` sets point B and `Escape` clears both
+pub fn build_loop_actions(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+    duration_bar: &Scale,
+) {
+    add_window_action(
+        window,
+        "loop-set-a",
+        &["a"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            move || {
+                let result = media_player.borrow_mut().set_loop_start_here();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+                update_duration_marks(&duration_bar, &media_player.borrow());
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "loop-set-b",
+        &["b"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            move || {
+                let result = media_player.borrow_mut().set_loop_end_here();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+                update_duration_marks(&duration_bar, &media_player.borrow());
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "loop-clear",
+        &["Escape"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            move || {
+                let result = media_player.borrow_mut().clear_loop();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+                update_duration_marks(&duration_bar, &media_player.borrow());
+            }
+        ),
+    );
+}
+
+/// Chapter shortcuts: `n` jumps to the next chapter and `p` to the previous one
+pub fn build_chapter_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_action(
+        window,
+        "chapter-next",
+        &["n"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                if let Err(err) = media_player.borrow().next_chapter() {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "chapter-previous",
+        &["p"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                if let Err(err) = media_player.borrow().previous_chapter() {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+}
+
+/// Label with the title of the chapter being played
+pub fn build_chapter_label(media_player: &MediaPlayerRef) -> Label {
+    let chapter_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .margin_start(6)
+        .build();
+
+    glib::timeout_add_seconds_local(
+        1,
+        clone!(
+            #[weak]
+            media_player,
+            #[weak]
+            chapter_label,
+            #[upgrade_or]
+            glib::ControlFlow::Break,
+            move || {
+                let media_player_ref = media_player.borrow();
+                let title = media_player_ref
+                    .current_chapter()
+                    .map(|index| media_player_ref.chapters()[index].title.clone());
+                chapter_label.set_visible(title.is_some());
+                chapter_label.set_label(&title.unwrap_or_default());
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+
+    chapter_label
+}
+
+/// Progress bar showing the buffer fill level and reconnections of network streams
+pub fn build_buffering_indicator(media_player: &MediaPlayerRef) -> gtk::ProgressBar {
+    let buffering_bar = gtk::ProgressBar::builder()
+        .show_text(true)
+        .visible(false)
+        .margin_start(6)
+        .margin_end(6)
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        buffering_bar,
+        move |event| match *event {
+            PlayerEvent::Buffering(percent) => {
+                buffering_bar.set_visible(percent < 100);
+                buffering_bar.set_fraction(f64::from(percent) / 100.0);
+                buffering_bar.set_text(Some(&format!("Buffering {}%", percent)));
+            }
+            PlayerEvent::Reconnecting { attempt } => {
+                buffering_bar.set_visible(true);
+                buffering_bar.set_fraction(0.0);
+                buffering_bar.set_text(Some(&format!(
+                    "Connection lost, reconnecting (attempt {})",
+                    attempt
+                )));
+            }
+            _ => (),
+        }
+    ));
+
+    buffering_bar
+}
+
+/// "LIVE" badge with the latency behind the live edge, shown for live sources
+pub fn build_live_indicator(media_player: &MediaPlayerRef) -> Label {
+    let live_label = Label::builder()
+        .halign(gtk::Align::End)
+        .margin_end(6)
+        .visible(false)
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        live_label,
+        move |event| {
+            let PlayerEvent::SeekingChanged { live, .. } = *event else {
+                return;
+            };
+            live_label.set_visible(live);
+            let latency = media_player
+                .borrow()
+                .live_latency()
+                .map_or_else(String::new, |latency| {
+                    format!(" (latency {} ms)", latency.mseconds())
+                });
+            live_label.set_markup(&format!("<b>● LIVE</b>{}", latency));
+        }
+    ));
+
+    live_label
+}
+
+/// Ask for a file or a folder with a native dialog, with `name` as the suggested file name
+/// when saving
+fn choose_file(
+    window: &ApplicationWindow,
+    title: &str,
+    action: gtk::FileChooserAction,
+    accept_label: &str,
+    name: Option<&str>,
+    on_chosen: impl Fn(PathBuf) + 'static,
+) {
+    let chooser = gtk::FileChooserNative::new(
+        Some(title),
+        Some(window),
+        action,
+        Some(accept_label),
+        Some("Cancel"),
+    );
+    if let Some(name) = name {
+        chooser.set_current_name(name);
+    }
+
+    // Native dialogs are not kept alive by GTK, the reference is released on response
+    let chooser_ref = Rc::new(RefCell::new(Some(chooser.clone())));
+    chooser.connect_response(move |chooser, response| {
+        chooser_ref.borrow_mut().take();
+        if response == ResponseType::Accept
+            && let Some(path) = chooser.file().and_then(|file| file.path())
+        {
+            on_chosen(path);
+        }
+    });
+    chooser.show();
+}
+
+/// `Ctrl+O` opens a file, the history entries of the menu and the start page open the
+/// recent media
+pub fn build_history_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_action(
+        window,
+        "open-file",
+        &["<Control>o"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                choose_file(
+                    &window,
+                    "Open File",
+                    gtk::FileChooserAction::Open,
+                    "Open",
+                    None,
+                    clone!(
+                        #[weak]
+                        window,
+                        #[weak]
+                        media_player,
+                        move |path| {
+                            let result = glib::filename_to_uri(&path, None)
+                                .map_err(|err| err.to_string())
+                                .and_then(|uri| {
+                                    let mut media_player = media_player.borrow_mut();
+                                    // HLS manifests are `.m3u8` files played as a media
+                                    let result = match Playlist::load(&uri) {
+                                        Ok(Some(playlist)) => media_player.set_queue(playlist, 0),
+                                        Ok(None) => media_player.open_uri(&uri, &OriginType::File),
+                                        Err(err) => Err(err),
+                                    };
+                                    result.map_err(|err| err.to_string())
+                                });
+                            if let Err(err) = result {
+                                error_dialog(&window, &err);
+                            }
+                        }
+                    ),
+                );
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "open-recent",
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |uri| {
+                let result = media_player.borrow_mut().open_recent(uri);
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "clear-history",
+        &[],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                let result = media_player.borrow_mut().clear_history();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    // Shown as a check item by the menu
+    let enabled = media_player.borrow().is_history_enabled();
+    let action = gio::SimpleAction::new_stateful("history-enabled", None, &enabled.to_variant());
+    action.connect_activate(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |action, _| {
+            let enabled = !action
+                .state()
+                .and_then(|state| state.get::<bool>())
+                .unwrap_or(true);
+            action.set_state(&enabled.to_variant());
+            let result = media_player.borrow_mut().set_history_enabled(enabled);
+            if let Err(err) = result {
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+    window.add_action(&action);
+}
+
+/// Playlist shortcuts: `>` plays the next media of the queue and `<` the previous one
+pub fn build_playlist_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_action(
+        window,
+        "playlist-next",
+        &["greater"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                let result = media_player.borrow_mut().play_next();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "playlist-previous",
+        &["less"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                let result = media_player.borrow_mut().play_previous();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    // The format follows the extension: m3u, m3u8, pls or xspf
+    add_window_action(
+        window,
+        "playlist-save",
+        &[],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                choose_file(
+                    &window,
+                    "Save Playlist",
+                    gtk::FileChooserAction::Save,
+                    "Save",
+                    Some("playlist.m3u"),
+                    clone!(
+                        #[weak]
+                        window,
+                        #[weak]
+                        media_player,
+                        move |path| {
+                            if let Err(err) = media_player.borrow().save_queue(&path) {
+                                error_dialog(&window, &format!("{}", err));
+                            }
+                        }
+                    ),
+                );
+            }
+        ),
+    );
+}
+
+/// Page shown in place of the video until a media is opened, listing the recent media
+pub fn build_start_page(media_player: &MediaPlayerRef) -> gtk::Box {
+    let start_page = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .valign(gtk::Align::Center)
+        .halign(gtk::Align::Center)
+        .spacing(12)
+        .build();
+
+    let title_label = Label::builder()
+        .label("<big><b>Recent Media</b></big>")
+        .use_markup(true)
+        .build();
+
+    let recent_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["rich-list"])
+        .build();
+    recent_list.set_placeholder(Some(&Label::new(Some("No recent media"))));
+
+    let open_button = Button::builder()
+        .label("Open File…")
+        .action_name("win.open-file")
+        .halign(gtk::Align::Center)
+        .build();
+
+    let fill_list = move |list: &gtk::ListBox, media_player: &MediaPlayer| {
+        while let Some(row) = list.row_at_index(0) {
+            list.remove(&row);
+        }
+        for recent in media_player.recent_media() {
+            let details = match recent.position {
+                Some(position) => format!(
+                    "{} · resume at {}",
+                    recent.opened_label(),
+                    short_time(position)
+                ),
+                None => recent.opened_label(),
+            };
+            let row_box = gtk::Box::builder()
+                .orientation(gtk::Orientation::Vertical)
+                .spacing(2)
+                .build();
+            row_box.append(
+                &Label::builder()
+                    .label(&recent.title)
+                    .halign(gtk::Align::Start)
+                    .build(),
+            );
+            row_box.append(
+                &Label::builder()
+                    .label(&details)
+                    .halign(gtk::Align::Start)
+                    .css_classes(["dim-label"])
+                    .build(),
+            );
+            let row = gtk::ListBoxRow::builder()
+                .child(&row_box)
+                .action_name("win.open-recent")
+                .action_target(&recent.uri.to_variant())
+                .tooltip_text(&recent.uri)
+                .build();
+            list.append(&row);
+        }
+    };
+    fill_list(&recent_list, &media_player.borrow());
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        recent_list,
+        move |event| {
+            if matches!(event, PlayerEvent::HistoryChanged) {
+                fill_list(&recent_list, &media_player.borrow());
+            }
+        }
+    ));
+
+    start_page.append(&title_label);
+    start_page.append(&recent_list);
+    start_page.append(&open_button);
+
+    start_page
+}
+
+/// Library actions: `Ctrl+L` shows the library window. Its rows and buttons use the
+/// `library` actions of the window only, so the shortcuts of the player window do not
+/// fire while typing a search.
+pub fn build_library_actions(
+    media_player: &MediaPlayerRef,
+    library: &LibraryRef,
+    window: &ApplicationWindow,
+) {
+    let actions = gio::SimpleActionGroup::new();
+    let library_window = build_library_window(library, window, &actions);
+    add_window_action(
+        window,
+        "library",
+        &["<Control>l"],
+        clone!(
+            #[weak]
+            library_window,
+            move || library_window.present()
+        ),
+    );
+
+    let open_action = gio::SimpleAction::new("open", Some(glib::VariantTy::STRING));
+    open_action.connect_activate(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_, parameter| {
+            let Some(uri) = parameter.and_then(|parameter| parameter.str()) else {
+                return;
+            };
+            let result = media_player.borrow_mut().open_uri(uri, &OriginType::File);
+            if let Err(err) = result {
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+    actions.add_action(&open_action);
+
+    let add_folder_action = gio::SimpleAction::new("add-folder", None);
+    add_folder_action.connect_activate(clone!(
+        #[weak]
+        window,
+        #[weak]
+        library,
+        move |_, _| {
+            choose_file(
+                &window,
+                "Add Folder",
+                gtk::FileChooserAction::SelectFolder,
+                "Add",
+                None,
+                clone!(
+                    #[weak]
+                    window,
+                    #[weak]
+                    library,
+                    move |folder| {
+                        let result = library.borrow_mut().add_folder(&folder);
+                        if let Err(err) = result {
+                            error_dialog(&window, &format!("{}", err));
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+    actions.add_action(&add_folder_action);
+
+    let remove_folder_action =
+        gio::SimpleAction::new("remove-folder", Some(glib::VariantTy::STRING));
+    remove_folder_action.connect_activate(clone!(
+        #[weak]
+        window,
+        #[weak]
+        library,
+        move |_, parameter| {
+            let Some(folder) = parameter.and_then(|parameter| parameter.str()) else {
+                return;
+            };
+            let result = library.borrow_mut().remove_folder(&PathBuf::from(folder));
+            if let Err(err) = result {
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+    actions.add_action(&remove_folder_action);
+
+    // The actions of the library window own the library
+    let rescan_action = gio::SimpleAction::new("rescan", None);
+    rescan_action.connect_activate(clone!(
+        #[strong]
+        library,
+        move |_, _| library.borrow_mut().rescan()
+    ));
+    actions.add_action(&rescan_action);
+}
+
+/// Window browsing the library: its folders, and the media matching the search
+fn build_library_window(
+    library: &LibraryRef,
+    window: &ApplicationWindow,
+    actions: &gio::SimpleActionGroup,
+) -> gtk::Window {
+    let search_entry = gtk::SearchEntry::builder()
+        .placeholder_text("Search, e.g. artist:queen >3:00")
+        .hexpand(true)
+        .build();
+    let add_button = Button::builder()
+        .label("Add Folder…")
+        .action_name("library.add-folder")
+        .build();
+    let rescan_button = Button::builder()
+        .icon_name("view-refresh-symbolic")
+        .tooltip_text("Index the folders again")
+        .action_name("library.rescan")
+        .build();
+    let search_box = gtk::Box::builder().spacing(6).build();
+    search_box.append(&search_entry);
+    search_box.append(&rescan_button);
+    search_box.append(&add_button);
+
+    let folder_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    folder_list.set_placeholder(Some(&Label::new(Some("Add a folder to index its media"))));
+
+    let status_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .css_classes(["dim-label"])
+        .build();
+
+    let results_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["rich-list"])
+        .build();
+    results_list.set_placeholder(Some(&Label::new(Some("No media found"))));
+    let results_window = gtk::ScrolledWindow::builder()
+        .child(&results_list)
+        .vexpand(true)
+        .build();
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .spacing(12)
+        .build();
+    content.append(&search_box);
+    content.append(&folder_list);
+    content.append(&status_label);
+    content.append(&results_window);
+
+    let library_window = gtk::Window::builder()
+        .title("Library")
+        .transient_for(window)
+        .default_width(640)
+        .default_height(480)
+        .hide_on_close(true)
+        .child(&content)
+        .build();
+    library_window.insert_action_group("library", Some(actions));
+
+    fill_library_folders(&folder_list, &library.borrow());
+    fill_library_results(&results_list, &status_label, "", &library.borrow());
+
+    search_entry.connect_search_changed(clone!(
+        #[weak]
+        library,
+        #[weak]
+        results_list,
+        #[weak]
+        status_label,
+        move |entry| {
+            fill_library_results(
+                &results_list,
+                &status_label,
+                &entry.text(),
+                &library.borrow(),
+            );
+        }
+    ));
+
+    library.borrow().connect_changed(clone!(
+        #[weak]
+        library,
+        #[weak]
+        folder_list,
+        #[weak]
+        results_list,
+        #[weak]
+        status_label,
+        #[weak]
+        search_entry,
+        move || {
+            let library = library.borrow();
+            fill_library_folders(&folder_list, &library);
+            fill_library_results(&results_list, &status_label, &search_entry.text(), &library);
+        }
+    ));
+
+    // The video shows once a media is opened
+    results_list.connect_row_activated(clone!(
+        #[weak]
+        library_window,
+        move |_, _| library_window.set_visible(false)
+    ));
+
+    library_window
+}
+
+/// List the library folders, with a button removing each of them
+fn fill_library_folders(list: &gtk::ListBox, library: &Library) {
+    while let Some(row) = list.row_at_index(0) {
+        list.remove(&row);
+    }
+    for folder in library.folders() {
+        let folder = folder.to_string_lossy().to_string();
+        let row_box = gtk::Box::builder().spacing(6).build();
+        row_box.append(
+            &Label::builder()
+                .label(&folder)
+                .halign(gtk::Align::Start)
+                .hexpand(true)
+                .build(),
+        );
+        row_box.append(
+            &Button::builder()
+                .icon_name("list-remove-symbolic")
+                .tooltip_text("Remove from the library")
+                .action_name("library.remove-folder")
+                .action_target(&folder.to_variant())
+                .build(),
+        );
+        list.append(&row_box);
+    }
+}
+
+/// List the media of the library matching `search`, and count them in `status`
+fn fill_library_results(list: &gtk::ListBox, status: &Label, search: &str, library: &Library) {
+    while let Some(row) = list.row_at_index(0) {
+        list.remove(&row);
+    }
+    let items = library.search(search);
+    for item in &items {
+        let thumbnail = gtk::Picture::builder()
+            .width_request(96)
+            .height_request(54)
+            .can_shrink(true)
+            .build();
+        if let Some(path) = &item.thumbnail {
+            thumbnail.set_filename(Some(path));
+        }
+        let text_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .valign(gtk::Align::Center)
+            .spacing(2)
+            .build();
+        text_box.append(
+            &Label::builder()
+                .label(&item.title)
+                .halign(gtk::Align::Start)
+                .build(),
+        );
+        text_box.append(
+            &Label::builder()
+                .label(&item.details())
+                .halign(gtk::Align::Start)
+                .css_classes(["dim-label"])
+                .build(),
+        );
+        let row_box = gtk::Box::builder().spacing(12).build();
+        row_box.append(&thumbnail);
+        row_box.append(&text_box);
+        let row = gtk::ListBoxRow::builder()
+            .child(&row_box)
+            .action_name("library.open")
+            .action_target(&item.uri.to_variant())
+            .tooltip_text(&item.uri)
+            .build();
+        list.append(&row);
+    }
+
+    let count = format!("{} media", items.len());
+    status.set_label(&if library.is_indexing() {
+        format!("{} · Indexing…", count)
+    } else {
+        count
+    });
+}
+
+/// Bookmark actions: `Ctrl+B` bookmarks the current position, the bookmarks are exported
+/// and imported as JSON or WebVTT chapters, and the media can be put aside to watch later
+pub fn build_bookmark_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_action(
+        window,
+        "bookmark-add",
+        &["<Control>b"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                let result = media_player.borrow_mut().add_bookmark_here();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    add_window_action(
+        window,
+        "bookmarks-import",
+        &[],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                choose_file(
+                    &window,
+                    "Import Bookmarks",
+                    gtk::FileChooserAction::Open,
+                    "Open",
+                    None,
+                    clone!(
+                        #[weak]
+                        window,
+                        #[weak]
+                        media_player,
+                        move |path| {
+                            let result = media_player.borrow_mut().import_bookmarks(&path);
+                            if let Err(err) = result {
+                                error_dialog(&window, &format!("{}", err));
+                            }
+                        }
+                    ),
+                );
+            }
+        ),
+    );
+
+    // The format follows the extension: json, or vtt for WebVTT chapters
+    add_window_action(
+        window,
+        "bookmarks-export",
+        &[],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                let name = format!(
+                    "{}-bookmarks.json",
+                    media_title(media_player.borrow().uri())
+                );
+                choose_file(
+                    &window,
+                    "Export Bookmarks",
+                    gtk::FileChooserAction::Save,
+                    "Save",
+                    Some(&name),
+                    clone!(
+                        #[weak]
+                        window,
+                        #[weak]
+                        media_player,
+                        move |path| {
+                            if let Err(err) = media_player.borrow().export_bookmarks(&path) {
+                                error_dialog(&window, &format!("{}", err));
+                            }
+                        }
+                    ),
+                );
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "open-watch-later",
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |uri| {
+                let result = media_player.borrow_mut().open_watch_later(uri);
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+
+    // Shown as a check item by the menu, following the media being played
+    let watch_later = media_player.borrow().is_watch_later();
+    let action = gio::SimpleAction::new_stateful("watch-later", None, &watch_later.to_variant());
+    action.connect_activate(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_, _| {
+            let watch_later = !media_player.borrow().is_watch_later();
+            let result = media_player.borrow_mut().set_watch_later(watch_later);
+            if let Err(err) = result {
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+    window.add_action(&action);
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        action,
+        move |event| {
+            if matches!(event, PlayerEvent::WatchLaterChanged) {
+                action.set_state(&media_player.borrow().is_watch_later().to_variant());
+            }
+        }
+    ));
+}
+
+/// Sidebar listing the bookmarks of the media, toggled by `F9`. Clicking a bookmark jumps
+/// to it.
+pub fn build_bookmarks_sidebar(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+) -> gtk::Revealer {
+    let title_label = Label::builder()
+        .label("<b>Bookmarks</b>")
+        .use_markup(true)
+        .halign(gtk::Align::Start)
+        .hexpand(true)
+        .build();
+    let add_button = Button::builder()
+        .icon_name("list-add-symbolic")
+        .tooltip_text("Bookmark the current position (Ctrl+B)")
+        .action_name("win.bookmark-add")
+        .build();
+    let header_box = gtk::Box::builder().spacing(6).build();
+    header_box.append(&title_label);
+    header_box.append(&add_button);
+
+    let bookmark_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["rich-list"])
+        .build();
+    bookmark_list.set_placeholder(Some(&Label::new(Some("No bookmark"))));
+    let list_window = gtk::ScrolledWindow::builder()
+        .child(&bookmark_list)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vexpand(true)
+        .build();
+
+    let sidebar_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .width_request(260)
+        .margin_start(12)
+        .spacing(6)
+        .build();
+    sidebar_box.append(&header_box);
+    sidebar_box.append(&list_window);
+
+    let sidebar = gtk::Revealer::builder()
+        .transition_type(gtk::RevealerTransitionType::SlideLeft)
+        .child(&sidebar_box)
+        .build();
+
+    let fill_list = clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |list: &gtk::ListBox| {
+            while let Some(row) = list.row_at_index(0) {
+                list.remove(&row);
+            }
+            for (index, bookmark) in media_player.borrow().bookmarks().into_iter().enumerate() {
+                list.append(&build_bookmark_row(
+                    &media_player,
+                    &window,
+                    index,
+                    &bookmark,
+                ));
+            }
+        }
+    );
+    fill_list(&bookmark_list);
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        bookmark_list,
+        move |event| {
+            if matches!(event, PlayerEvent::BookmarksChanged) {
+                fill_list(&bookmark_list);
+            }
+        }
+    ));
+
+    bookmark_list.connect_row_activated(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_, row| {
+            let Ok(index) = usize::try_from(row.index()) else {
+                return;
+            };
+            if let Err(err) = media_player.borrow().seek_bookmark(index) {
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+
+    let action = gio::SimpleAction::new_stateful("bookmarks-sidebar", None, &false.to_variant());
+    action.connect_activate(clone!(
+        #[weak]
+        sidebar,
+        move |action, _| {
+            let visible = !sidebar.reveals_child();
+            sidebar.set_reveal_child(visible);
+            action.set_state(&visible.to_variant());
+        }
+    ));
+    window.add_action(&action);
+    if let Some(app) = window.application() {
+        app.set_accels_for_action("win.bookmarks-sidebar", &["F9"]);
+    }
+
+    sidebar
+}
+
+/// Row of the bookmarks sidebar: the time, name and note of the bookmark, with buttons
+/// editing and removing it
+fn build_bookmark_row(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+    index: usize,
+    bookmark: &Bookmark,
+) -> gtk::Box {
+    let text_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .hexpand(true)
+        .spacing(2)
+        .build();
+    text_box.append(
+        &Label::builder()
+            .label(format!(
+                "{} · {}",
+                short_time(bookmark.position),
+                bookmark.name
+            ))
+            .halign(gtk::Align::Start)
+            .wrap(true)
+            .xalign(0.0)
+            .build(),
+    );
+    if !bookmark.note.is_empty() {
+        text_box.append(
+            &Label::builder()
+                .label(&bookmark.note)
+                .halign(gtk::Align::Start)
+                .wrap(true)
+                .xalign(0.0)
+                .css_classes(["dim-label"])
+                .build(),
+        );
+    }
+
+    let edit_button = Button::builder()
+        .icon_name("document-edit-symbolic")
+        .tooltip_text("Edit the name and the note")
+        .valign(gtk::Align::Center)
+        .build();
+    edit_button.connect_clicked(clone!(
         #[weak]
         window,
         #[weak]
         media_player,
-        #[upgrade_or]
-        glib::Propagation::Stop,
-        move |duration_b, _, new_pos| {
-            let mut media_player_ref = media_player.borrow_mut();
-            media_player_ref.set_user_is_seeking(true);
-            if media_player_ref.pause_player().is_err() {
-                error_dialog(&window, "There was a problem seeking the media.");
-                media_player_ref.set_user_is_seeking(false);
-                return glib::Propagation::Stop;
+        move |_| edit_bookmark_dialog(&media_player, &window, index)
+    ));
+    let remove_button = Button::builder()
+        .icon_name("list-remove-symbolic")
+        .tooltip_text("Remove the bookmark")
+        .valign(gtk::Align::Center)
+        .build();
+    remove_button.connect_clicked(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_| {
+            let result = media_player.borrow_mut().remove_bookmark(index);
+            if let Err(err) = result {
+                error_dialog(&window, &format!("{}", err));
             }
-            if let Some(duration) = media_player_ref.duration() {
-                let seek_pos = (new_pos / 100_f64) * duration.seconds_f64();
-                if let Err(err) =
-                    media_player_ref.seek_position(gstreamer::ClockTime::from_seconds_f64(seek_pos))
-                {
+        }
+    ));
+
+    let row_box = gtk::Box::builder().spacing(6).build();
+    row_box.append(&text_box);
+    row_box.append(&edit_button);
+    row_box.append(&remove_button);
+    row_box
+}
+
+/// Dialog editing the name and the note of a bookmark
+fn edit_bookmark_dialog(media_player: &MediaPlayerRef, window: &ApplicationWindow, index: usize) {
+    let Some(bookmark) = media_player.borrow().bookmarks().into_iter().nth(index) else {
+        return;
+    };
+    let dialog = Dialog::builder()
+        .title(format!("Bookmark at {}", short_time(bookmark.position)))
+        .transient_for(window)
+        .modal(true)
+        .default_width(360)
+        .build();
+
+    let name_entry = gtk::Entry::builder()
+        .text(&bookmark.name)
+        .activates_default(true)
+        .build();
+    let note_view = gtk::TextView::builder()
+        .wrap_mode(gtk::WrapMode::WordChar)
+        .height_request(120)
+        .build();
+    note_view.buffer().set_text(&bookmark.note);
+
+    let content_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .spacing(6)
+        .build();
+    content_box.append(
+        &Label::builder()
+            .label("Name")
+            .halign(gtk::Align::Start)
+            .build(),
+    );
+    content_box.append(&name_entry);
+    content_box.append(
+        &Label::builder()
+            .label("Note")
+            .halign(gtk::Align::Start)
+            .build(),
+    );
+    content_box.append(
+        &gtk::ScrolledWindow::builder()
+            .child(&note_view)
+            .vexpand(true)
+            .build(),
+    );
+    dialog.content_area().append(&content_box);
+
+    dialog.add_button("Cancel", ResponseType::Cancel);
+    dialog.add_button("Save", ResponseType::Accept);
+    dialog.set_default_response(ResponseType::Accept);
+    dialog.connect_response(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |dialog, response| {
+            if response == ResponseType::Accept {
+                let buffer = note_view.buffer();
+                let note = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+                let result =
+                    media_player
+                        .borrow_mut()
+                        .update_bookmark(index, &name_entry.text(), &note);
+                if let Err(err) = result {
                     error_dialog(&window, &format!("{}", err));
-                    media_player_ref.set_user_is_seeking(false);
-                    return glib::Propagation::Stop;
                 }
-                duration_b.set_value(new_pos);
             }
+            dialog.close();
+        }
+    ));
+    dialog.present();
+}
 
-            if media_player_ref.play_player().is_err() {
-                error_dialog(&window, "There was a problem seeking the media.");
-                media_player_ref.set_user_is_seeking(false);
-                return glib::Propagation::Stop;
+/// Clip shortcuts: `i` and `o` set the start and the end of the clip, `Ctrl+E` exports it.
+/// Returns the progress of the running export, hidden while idle.
+pub fn build_export_controls(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+    duration_bar: &Scale,
+) -> gtk::Box {
+    add_window_action(
+        window,
+        "clip-set-in",
+        &["i"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            move || {
+                let result = media_player.borrow_mut().set_clip_in_here();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+                update_duration_marks(&duration_bar, &media_player.borrow());
             }
+        ),
+    );
 
-            // Placeholder for future functionality
-            media_player_ref.set_user_is_seeking(false);
+    add_window_action(
+        window,
+        "clip-set-out",
+        &["o"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[weak]
+            duration_bar,
+            move || {
+                let result = media_player.borrow_mut().set_clip_out_here();
+                if let Err(err) = result {
+                    error_dialog(&window, &format!("{}", err));
+                }
+                update_duration_marks(&duration_bar, &media_player.borrow());
+            }
+        ),
+    );
 
-            glib::Propagation::Stop
+    let export_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(6)
+        .margin_start(6)
+        .margin_end(6)
+        .visible(false)
+        .build();
+    let export_bar = gtk::ProgressBar::builder()
+        .show_text(true)
+        .hexpand(true)
+        .valign(gtk::Align::Center)
+        .build();
+    let cancel_button = Button::builder().label("Cancel").build();
+    export_box.append(&export_bar);
+    export_box.append(&cancel_button);
+
+    let exporter: Rc<RefCell<Option<Transcoder>>> = Rc::new(RefCell::new(None));
+
+    cancel_button.connect_clicked(clone!(
+        #[strong]
+        exporter,
+        #[weak]
+        export_box,
+        move |_| {
+            if let Some(exporter) = exporter.borrow_mut().take() {
+                exporter.cancel();
+            }
+            export_box.set_visible(false);
+        }
+    ));
+
+    // Start exporting to `output`, the progress bar follows the export until it ends
+    let start_export = clone!(
+        #[weak]
+        window,
+        #[strong]
+        exporter,
+        #[weak]
+        export_box,
+        #[weak]
+        export_bar,
+        move |uri: &str, from: gstreamer::ClockTime, to: gstreamer::ClockTime, output: PathBuf| {
+            let format = EncodingFormat::from_path(&output).unwrap_or(EncodingFormat::Mkv);
+            let mut options = TranscodeOptions::new(format);
+            options.range = Some((from, to));
+            let result = Transcoder::start(
+                uri,
+                &output,
+                &options,
+                clone!(
+                    #[weak]
+                    window,
+                    #[strong]
+                    exporter,
+                    #[weak]
+                    export_box,
+                    #[weak]
+                    export_bar,
+                    move |event| {
+                        let failure = match event {
+                            TranscodeEvent::Progress(progress) => {
+                                export_bar.set_fraction(progress);
+                                return;
+                            }
+                            TranscodeEvent::Finished => None,
+                            TranscodeEvent::Failed(err) => Some(err),
+                        };
+                        export_box.set_visible(false);
+                        // The exporter is reporting this event, it is dropped afterwards
+                        glib::idle_add_local_once(clone!(
+                            #[strong]
+                            exporter,
+                            move || {
+                                exporter.borrow_mut().take();
+                            }
+                        ));
+                        if let Some(err) = failure {
+                            error_dialog(&window, &format!("Export failed: {}", err));
+                        }
+                    }
+                ),
+            );
+            match result {
+                Ok(started) => {
+                    let name = output
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    export_bar.set_fraction(0.0);
+                    export_bar.set_text(Some(&format!("Exporting {}", name)));
+                    export_box.set_visible(true);
+                    exporter.replace(Some(started));
+                }
+                Err(err) => error_dialog(&window, &format!("{}", err)),
+            }
+        }
+    );
+    let start_export = Rc::new(start_export);
+
+    add_window_action(
+        window,
+        "clip-export",
+        &["<Control>e"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            #[strong]
+            exporter,
+            move || {
+                if exporter.borrow().is_some() {
+                    error_dialog(&window, "A clip is already being exported");
+                    return;
+                }
+                let (uri, range) = {
+                    let media_player = media_player.borrow();
+                    (media_player.uri().to_string(), media_player.clip_range())
+                };
+                let (from, to) = match range {
+                    Ok(range) => range,
+                    Err(err) => {
+                        error_dialog(&window, &format!("{}", err));
+                        return;
+                    }
+                };
+                let start_export = start_export.clone();
+                choose_file(
+                    &window,
+                    "Export Clip",
+                    gtk::FileChooserAction::Save,
+                    "Save",
+                    Some(&format!("{}-clip.mkv", media_title(&uri))),
+                    move |output| start_export(&uri, from, to, output),
+                );
+            }
+        ),
+    );
+
+    export_box
+}
+
+/// Audio/video offset shortcuts: `]` delays the audio by 50 ms and `[` advances it
+pub fn build_av_offset_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    let actions: [(&str, &[&str], fn(i64) -> i64); 3] = [
+        ("av-offset-increase", &["bracketright"], |offset| {
+            offset + AV_OFFSET_STEP
+        }),
+        ("av-offset-decrease", &["bracketleft"], |offset| {
+            offset - AV_OFFSET_STEP
+        }),
+        ("av-offset-reset", &[], |_| 0),
+    ];
+    for (name, accels, offset) in actions {
+        add_window_action(
+            window,
+            name,
+            accels,
+            clone!(
+                #[weak]
+                window,
+                #[weak]
+                media_player,
+                move || {
+                    let av_offset = offset(media_player.borrow().av_offset());
+                    if let Err(err) = media_player.borrow_mut().set_av_offset(av_offset) {
+                        error_dialog(&window, &format!("{}", err));
+                    }
+                }
+            ),
+        );
+    }
+}
+
+/// Start or stop the recording with the `win.record-toggle` action, bound to "r"
+pub fn build_recording_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_action(
+        window,
+        "record-toggle",
+        &["r"],
+        clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move || {
+                if let Err(err) = media_player.borrow_mut().toggle_recording() {
+                    error_dialog(&window, &format!("{}", err));
+                }
+            }
+        ),
+    );
+}
+
+/// Header bar button of the `win.record-toggle` action, reflecting the recording state
+pub fn build_record_button(media_player: &MediaPlayerRef) -> Button {
+    let record_button = Button::builder()
+        .icon_name("media-record")
+        .tooltip_text("Start recording (r)")
+        .action_name("win.record-toggle")
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        record_button,
+        move |event| {
+            let PlayerEvent::RecordingChanged { recording } = *event else {
+                return;
+            };
+            if recording {
+                record_button.set_icon_name("media-playback-stop");
+                record_button.set_tooltip_text(Some("Stop recording (r)"));
+            } else {
+                record_button.set_icon_name("media-record");
+                record_button.set_tooltip_text(Some("Start recording (r)"));
+            }
         }
     ));
 
+    record_button
+}
+
+/// Indicator shown while recording, with the elapsed time and the output file
+pub fn build_recording_indicator(media_player: &MediaPlayerRef) -> Label {
+    let recording_label = Label::builder()
+        .halign(gtk::Align::End)
+        .margin_end(6)
+        .visible(false)
+        .build();
+
     glib::timeout_add_seconds_local(
         1,
         clone!(
             #[weak]
             media_player,
             #[weak]
-            duration_bar,
+            recording_label,
             #[upgrade_or]
             glib::ControlFlow::Break,
             move || {
                 let media_player_ref = media_player.borrow();
-                if !media_player_ref.seek_enabled() {
-                    duration_bar.set_value(100.0);
-                    return glib::ControlFlow::Continue;
-                }
-                if media_player_ref.playing() && !media_player_ref.user_is_seeking() {
-                    if let (Ok(position), Some(duration)) =
-                        (media_player_ref.get_position(), media_player_ref.duration())
-                    {
-                        let bar_position =
-                            (100_f64 / duration.seconds_f64()) * position.seconds_f64();
-                        duration_bar.set_value(bar_position);
-                    }
+                let status = media_player_ref.recording_status();
+                recording_label.set_visible(status.is_some());
+                if let Some((path, elapsed)) = status {
+                    let seconds = elapsed.as_secs();
+                    recording_label.set_markup(&format!(
+                        "<span foreground=\"red\"><b>● REC</b></span> {:02}:{:02}:{:02}",
+                        seconds / 3600,
+                        seconds / 60 % 60,
+                        seconds % 60
+                    ));
+                    recording_label.set_tooltip_text(Some(&path.display().to_string()));
                 }
                 glib::ControlFlow::Continue
             }
         ),
     );
+
+    recording_label
 }
 
 pub fn error_dialog(window: &ApplicationWindow, message: &str) {
@@ -168,68 +1988,347 @@ pub fn build_buttons(media_player: &MediaPlayerRef, window: &ApplicationWindow)
         }
     ));
 
-    let forward_button = Button::builder()
-        .icon_name("media-seek-forward")
-        .margin_top(2)
-        .margin_bottom(2)
+    let forward_button = Button::builder()
+        .icon_name("media-seek-forward")
+        .margin_top(2)
+        .margin_bottom(2)
+        .margin_start(6)
+        .margin_end(6)
+        .halign(gtk::Align::Center)
+        .build();
+
+    forward_button.connect_clicked(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_| {
+            if let Err(err) = media_player.borrow().move_forward() {
+                println!("Error seeking forward: {:?}", err);
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+
+    let backward_button = Button::builder()
+        .icon_name("media-seek-backward")
+        .margin_top(2)
+        .margin_bottom(2)
+        .margin_start(6)
+        .margin_end(6)
+        .halign(gtk::Align::Center)
+        .build();
+
+    backward_button.connect_clicked(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        move |_| {
+            if let Err(err) = media_player.borrow().move_backward() {
+                println!("Error seeking backward: {:?}", err);
+                error_dialog(&window, &format!("{}", err));
+            }
+        }
+    ));
+
+    // Seeking is not possible in live streams
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        forward_button,
+        #[weak]
+        backward_button,
+        move |event| {
+            if let PlayerEvent::SeekingChanged { seekable, .. } = *event {
+                forward_button.set_sensitive(seekable);
+                backward_button.set_sensitive(seekable);
+            }
+        }
+    ));
+
+    let button_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .halign(gtk::Align::Center)
+        .spacing(6)
+        .margin_top(2)
+        .margin_bottom(2)
+        .build();
+
+    let previous_chapter_button = Button::builder()
+        .icon_name("media-skip-backward")
+        .action_name("win.chapter-previous")
+        .margin_top(2)
+        .margin_bottom(2)
+        .margin_start(6)
+        .margin_end(6)
+        .halign(gtk::Align::Center)
+        .build();
+
+    let next_chapter_button = Button::builder()
+        .icon_name("media-skip-forward")
+        .action_name("win.chapter-next")
+        .margin_top(2)
+        .margin_bottom(2)
+        .margin_start(6)
+        .margin_end(6)
+        .halign(gtk::Align::Center)
+        .build();
+
+    button_box.append(&previous_chapter_button);
+    button_box.append(&backward_button);
+    button_box.append(&pause_button);
+    button_box.append(&start_button);
+    button_box.append(&stop_button);
+    button_box.append(&forward_button);
+    button_box.append(&next_chapter_button);
+
+    button_box
+}
+
+/// Accessor of one picture adjustment
+type BalanceField = fn(&mut VideoBalance) -> &mut f64;
+
+/// Header bar button opening the picture adjustments, with a slider for each and a reset
+pub fn build_video_adjustments(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+) -> gtk::MenuButton {
+    let fields: [(&str, f64, f64, BalanceField); 4] = [
+        ("Brightness", -1.0, 1.0, |balance| &mut balance.brightness),
+        ("Contrast", 0.0, 2.0, |balance| &mut balance.contrast),
+        ("Saturation", 0.0, 2.0, |balance| &mut balance.saturation),
+        ("Hue", -1.0, 1.0, |balance| &mut balance.hue),
+    ];
+
+    let grid = gtk::Grid::builder()
+        .row_spacing(6)
+        .column_spacing(12)
+        .margin_top(6)
+        .margin_bottom(6)
         .margin_start(6)
         .margin_end(6)
-        .halign(gtk::Align::Center)
         .build();
 
-    forward_button.connect_clicked(clone!(
+    let mut sliders = Vec::new();
+    for (row, (label, min, max, field)) in fields.into_iter().enumerate() {
+        let mut balance = media_player.borrow().video_balance();
+        let slider = Scale::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .adjustment(&gtk::Adjustment::new(
+                *field(&mut balance),
+                min,
+                max,
+                0.01,
+                0.1,
+                0.0,
+            ))
+            .digits(2)
+            .draw_value(true)
+            .width_request(200)
+            .build();
+        let handler = slider.connect_value_changed(clone!(
+            #[weak]
+            window,
+            #[weak]
+            media_player,
+            move |slider| {
+                let mut balance = media_player.borrow().video_balance();
+                *field(&mut balance) = slider.value();
+                if let Err(err) = media_player.borrow_mut().set_video_balance(balance) {
+                    error_dialog(&window, &format!("Error adjusting the picture: {}", err));
+                }
+            }
+        ));
+
+        let label = Label::builder()
+            .label(label)
+            .halign(gtk::Align::Start)
+            .build();
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(&slider, 1, row as i32, 1, 1);
+        sliders.push((slider, field, handler));
+    }
+
+    let reset_button = Button::builder()
+        .label("Reset")
+        .halign(gtk::Align::End)
+        .build();
+    reset_button.connect_clicked(clone!(
         #[weak]
         window,
         #[weak]
         media_player,
         move |_| {
-            if let Err(err) = media_player.borrow().move_forward() {
-                println!("Error seeking forward: {:?}", err);
-                error_dialog(&window, &format!("{}", err));
+            let result = media_player
+                .borrow_mut()
+                .set_video_balance(VideoBalance::default());
+            if let Err(err) = result {
+                error_dialog(&window, &format!("Error resetting the picture: {}", err));
             }
         }
     ));
+    grid.attach(&reset_button, 1, sliders.len() as i32, 1, 1);
 
-    let backward_button = Button::builder()
-        .icon_name("media-seek-backward")
-        .margin_top(2)
-        .margin_bottom(2)
-        .margin_start(6)
-        .margin_end(6)
-        .halign(gtk::Align::Center)
-        .build();
-
-    backward_button.connect_clicked(clone!(
-        #[weak]
-        window,
+    // The sliders follow the adjustments loaded for each media and the reset
+    let slider_refs: Vec<_> = sliders
+        .into_iter()
+        .map(|(slider, field, handler)| (slider.downgrade(), field, handler))
+        .collect();
+    media_player.borrow().connect_event(clone!(
         #[weak]
         media_player,
-        move |_| {
-            if let Err(err) = media_player.borrow().move_backward() {
-                println!("Error seeking backward: {:?}", err);
-                error_dialog(&window, &format!("{}", err));
+        move |event| {
+            if !matches!(event, PlayerEvent::VideoBalanceChanged) {
+                return;
+            }
+            let mut balance = media_player.borrow().video_balance();
+            for (slider, field, handler) in &slider_refs {
+                if let Some(slider) = slider.upgrade() {
+                    // Showing the adjustments must not apply them again
+                    slider.block_signal(handler);
+                    slider.set_value(*field(&mut balance));
+                    slider.unblock_signal(handler);
+                }
             }
         }
     ));
 
-    let button_box = gtk::Box::builder()
-        .orientation(gtk::Orientation::Horizontal)
-        .halign(gtk::Align::Center)
-        .spacing(6)
-        .margin_top(2)
-        .margin_bottom(2)
-        .build();
+    let popover = gtk::Popover::builder().child(&grid).build();
+    gtk::MenuButton::builder()
+        .icon_name("applications-graphics-symbolic")
+        .tooltip_text("Video adjustments")
+        .popover(&popover)
+        .build()
+}
 
-    button_box.append(&backward_button);
-    button_box.append(&pause_button);
-    button_box.append(&start_button);
-    button_box.append(&stop_button);
-    button_box.append(&forward_button);
+/// Register a window action changing the geometry of the picture
+fn add_transform_action(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+    name: &str,
+    accels: &[&str],
+    update: fn(&mut VideoTransform),
+) {
+    add_window_action(
+        window,
+        name,
+        accels,
+        clone!(
+            #[weak]
+            media_player,
+            move || media_player.borrow().update_video_transform(update)
+        ),
+    );
+}
 
-    button_box
+/// Video geometry shortcuts: `Ctrl+R` and `Ctrl+Shift+R` rotate, `h` and `v` flip,
+/// `+` and `-` zoom, `Alt+arrows` pan the zoomed picture and `0` resets everything
+pub fn build_video_transform_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    let actions: [(&str, &[&str], fn(&mut VideoTransform)); 11] = [
+        ("rotate-clockwise", &["<Control>r"], |transform| {
+            transform.rotate(true)
+        }),
+        (
+            "rotate-counterclockwise",
+            &["<Control><Shift>r"],
+            |transform| transform.rotate(false),
+        ),
+        ("flip-horizontal", &["h"], |transform| transform.flip(true)),
+        ("flip-vertical", &["v"], |transform| transform.flip(false)),
+        ("zoom-in", &["plus", "equal", "KP_Add"], |transform| {
+            transform.zoom_by(1.25)
+        }),
+        ("zoom-out", &["minus", "KP_Subtract"], |transform| {
+            transform.zoom_by(0.8)
+        }),
+        ("pan-left", &["<Alt>Left"], |transform| {
+            transform.pan_by(-0.1, 0.0)
+        }),
+        ("pan-right", &["<Alt>Right"], |transform| {
+            transform.pan_by(0.1, 0.0)
+        }),
+        ("pan-up", &["<Alt>Up"], |transform| {
+            transform.pan_by(0.0, -0.1)
+        }),
+        ("pan-down", &["<Alt>Down"], |transform| {
+            transform.pan_by(0.0, 0.1)
+        }),
+        ("video-reset", &["0"], |transform| transform.reset()),
+    ];
+    for (name, accels, update) in actions {
+        add_transform_action(media_player, window, name, accels, update);
+    }
+
+    add_window_string_action(
+        window,
+        "crop",
+        clone!(
+            #[weak]
+            media_player,
+            move |preset| {
+                let crop = video_transform::aspect_preset(preset);
+                media_player
+                    .borrow()
+                    .update_video_transform(|transform| transform.crop = crop);
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "aspect-ratio",
+        clone!(
+            #[weak]
+            media_player,
+            move |preset| {
+                let aspect_ratio = video_transform::aspect_preset(preset);
+                media_player
+                    .borrow()
+                    .update_video_transform(|transform| transform.aspect_ratio = aspect_ratio);
+            }
+        ),
+    );
+}
+
+/// Deinterlacing entries of the "Video" menu
+pub fn build_deinterlace_actions(media_player: &MediaPlayerRef, window: &ApplicationWindow) {
+    add_window_string_action(
+        window,
+        "deinterlace",
+        clone!(
+            #[weak]
+            media_player,
+            move |value| {
+                let Ok(mode) = Deinterlace::from_str(value, true) else {
+                    return;
+                };
+                let method = media_player.borrow().deinterlace_method();
+                media_player.borrow_mut().set_deinterlace(mode, method);
+            }
+        ),
+    );
+
+    add_window_string_action(
+        window,
+        "deinterlace-method",
+        clone!(
+            #[weak]
+            media_player,
+            move |value| {
+                let Ok(method) = DeinterlaceMethod::from_str(value, true) else {
+                    return;
+                };
+                let mode = media_player.borrow().deinterlace();
+                media_player.borrow_mut().set_deinterlace(mode, method);
+            }
+        ),
+    );
 }
 
-pub fn build_volume_controls(media_player: &MediaPlayerRef, window: &ApplicationWindow) -> gtk::Box {
+pub fn build_volume_controls(
+    media_player: &MediaPlayerRef,
+    window: &ApplicationWindow,
+) -> gtk::Box {
     let volume_box = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
         .halign(gtk::Align::Fill)
@@ -270,18 +2369,15 @@ pub fn build_volume_controls(media_player: &MediaPlayerRef, window: &Application
     ));
 
     // Volume label
-    let volume_label = Label::builder()
-        .label("Volume:")
-        .margin_start(6)
-        .build();
+    let volume_label = Label::builder().label("Volume:").margin_start(6).build();
 
-    // Volume slider (0-100)
+    // Volume slider, in percent (0-100, or 0-200 with the boost)
     let volume_slider = Scale::builder()
         .orientation(gtk::Orientation::Horizontal)
         .adjustment(&gtk::Adjustment::new(
             media_player.borrow().get_volume() * 100.0,
             0.0,
-            100.0,
+            media_player.borrow().max_volume() * 100.0,
             1.0,
             5.0,
             0.0,
@@ -290,34 +2386,420 @@ pub fn build_volume_controls(media_player: &MediaPlayerRef, window: &Application
         .width_request(150)
         .build();
 
+    // Current volume in percent and dB
+    let volume_level_label = Label::builder()
+        .label(volume_text(&media_player.borrow()))
+        .width_chars(16)
+        .build();
+
     volume_slider.connect_value_changed(clone!(
         #[weak]
         window,
         #[weak]
         media_player,
+        #[weak]
+        volume_level_label,
         move |slider| {
             let volume = slider.value() / 100.0;
             if let Err(err) = media_player.borrow_mut().set_volume(volume) {
                 error_dialog(&window, &format!("Error setting volume: {}", err));
             }
+            volume_level_label.set_label(&volume_text(&media_player.borrow()));
+        }
+    ));
+
+    // Boost toggle, allowing the volume to go above 100%
+    let boost_button = gtk::CheckButton::builder()
+        .label("Boost")
+        .active(media_player.borrow().max_volume() > 1.0)
+        .build();
+
+    boost_button.connect_toggled(clone!(
+        #[weak]
+        window,
+        #[weak]
+        media_player,
+        #[weak]
+        volume_slider,
+        move |button| {
+            if let Err(err) = media_player
+                .borrow_mut()
+                .set_volume_boost(button.is_active())
+            {
+                error_dialog(&window, &format!("Error setting volume boost: {}", err));
+                return;
+            }
+            // Keep the borrow short, lowering the upper bound may change the slider value
+            let max_volume = media_player.borrow().max_volume();
+            volume_slider.adjustment().set_upper(max_volume * 100.0);
+        }
+    ));
+
+    // Playbin may change the volume on its own, e.g. from the system mixer
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        volume_slider,
+        #[weak]
+        volume_level_label,
+        #[weak]
+        mute_button,
+        #[weak]
+        media_player,
+        move |event| {
+            let PlayerEvent::VolumeChanged { volume, muted } = *event else {
+                return;
+            };
+            // Only move the slider on real changes, to avoid feeding the value back to playbin
+            if (volume_slider.value() - volume * 100.0).abs() > 0.5 {
+                volume_slider.set_value(volume * 100.0);
+            }
+            volume_level_label.set_label(&volume_text(&media_player.borrow()));
+            mute_button.set_icon_name(if muted {
+                "audio-volume-muted-symbolic"
+            } else {
+                "audio-volume-high-symbolic"
+            });
         }
     ));
 
     volume_box.append(&volume_label);
     volume_box.append(&volume_slider);
+    volume_box.append(&volume_level_label);
+    volume_box.append(&boost_button);
     volume_box.append(&mute_button);
 
     volume_box
 }
 
+fn volume_text(media_player: &MediaPlayer) -> String {
+    let volume_db = media_player.get_volume_db();
+    if volume_db.is_finite() {
+        format!(
+            "{:.0}% ({:+.1} dB)",
+            media_player.get_volume() * 100.0,
+            volume_db
+        )
+    } else {
+        format!("{:.0}% (-inf dB)", media_player.get_volume() * 100.0)
+    }
+}
+
+/// Text shown over the video for a player event, if any
+fn osd_text(event: &PlayerEvent) -> Option<String> {
+    match event {
+        PlayerEvent::Osd(message) => Some(message.clone()),
+        PlayerEvent::VolumeChanged { muted: true, .. } => Some("Muted".to_string()),
+        PlayerEvent::VolumeChanged { volume, .. } => Some(format!("Volume {:.0}%", volume * 100.0)),
+        PlayerEvent::RecordingChanged { recording: true } => Some("Recording".to_string()),
+        PlayerEvent::RecordingChanged { recording: false } => Some("Recording stopped".to_string()),
+        PlayerEvent::Reconnecting { attempt } => Some(format!("Reconnecting ({})", attempt)),
+        _ => None,
+    }
+}
+
+/// Cover art shown over the empty picture of audio-only media, with the `cover` visualization
+pub fn build_cover_art(media_player: &MediaPlayerRef) -> gtk::Picture {
+    let cover_picture = gtk::Picture::builder()
+        .can_shrink(true)
+        .visible(false)
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        cover_picture,
+        move |event| {
+            if !matches!(event, PlayerEvent::VisualizationChanged) {
+                return;
+            }
+            let media_player = media_player.borrow();
+            let cover_art = media_player.cover_art().filter(|_| {
+                media_player.is_audio_only() && media_player.visualization() == Visualization::Cover
+            });
+            cover_picture.set_paintable(cover_art);
+            cover_picture.set_visible(cover_art.is_some());
+        }
+    ));
+
+    cover_picture
+}
+
+/// Label showing the player messages over the video for a short time
+pub fn build_osd(media_player: &MediaPlayerRef) -> Label {
+    let osd_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .valign(gtk::Align::Start)
+        .margin_top(12)
+        .margin_start(12)
+        .visible(false)
+        .css_classes(["osd"])
+        .build();
+
+    // A new message restarts the delay, so the label hides after the last one
+    let hide_timeout: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        osd_label,
+        move |event| {
+            let Some(text) = osd_text(event) else {
+                return;
+            };
+            osd_label.set_label(&text);
+            osd_label.set_visible(true);
+
+            if let Some(timeout) = hide_timeout.borrow_mut().take() {
+                timeout.remove();
+            }
+            let timeout = glib::timeout_add_local_once(
+                OSD_DURATION,
+                clone!(
+                    #[weak]
+                    osd_label,
+                    #[strong]
+                    hide_timeout,
+                    move || {
+                        hide_timeout.borrow_mut().take();
+                        osd_label.set_visible(false);
+                    }
+                ),
+            );
+            *hide_timeout.borrow_mut() = Some(timeout);
+        }
+    ));
+
+    osd_label
+}
+
+pub fn build_info_panel(media_player: &MediaPlayerRef) -> gtk::Box {
+    let info_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .halign(gtk::Align::Start)
+        .spacing(2)
+        .margin_top(6)
+        .margin_start(6)
+        .build();
+
+    let normalization_label = Label::builder()
+        .label("Normalization: -")
+        .halign(gtk::Align::Start)
+        .build();
+
+    let variant_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .visible(false)
+        .build();
+
+    // Quality of adaptive streams: "Auto" followed by the variants of the manifest
+    let quality_dropdown = gtk::DropDown::from_strings(&["Auto"]);
+    let quality_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(6)
+        .visible(false)
+        .build();
+    quality_box.append(&Label::new(Some("Quality:")));
+    quality_box.append(&quality_dropdown);
+
+    let quality_handler = quality_dropdown.connect_selected_notify(clone!(
+        #[weak]
+        media_player,
+        move |dropdown| {
+            let mut media_player_ref = media_player.borrow_mut();
+            let quality = match dropdown.selected() {
+                0 | gtk::INVALID_LIST_POSITION => QualityLock::Auto,
+                index => match media_player_ref.variants().get(index as usize - 1) {
+                    Some(Variant {
+                        height: Some(height),
+                        ..
+                    }) => QualityLock::Resolution(*height),
+                    Some(variant) => QualityLock::Bitrate(variant.bandwidth),
+                    None => QualityLock::Auto,
+                },
+            };
+            media_player_ref.set_quality(quality);
+        }
+    ));
+    let quality_handler = Rc::new(quality_handler);
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        quality_dropdown,
+        #[weak]
+        quality_box,
+        #[strong]
+        quality_handler,
+        move |event| {
+            if !matches!(event, PlayerEvent::VariantsChanged) {
+                return;
+            }
+            let (labels, selected) = {
+                let media_player_ref = media_player.borrow();
+                let variants = media_player_ref.variants();
+                let mut labels = vec!["Auto".to_string()];
+                labels.extend(variants.iter().map(Variant::label));
+                let selected = match media_player_ref.quality() {
+                    QualityLock::Resolution(height) => variants
+                        .iter()
+                        .rposition(|variant| variant.height == Some(height))
+                        .map_or(0, |index| index + 1),
+                    QualityLock::Bitrate(bitrate) => variants
+                        .iter()
+                        .rposition(|variant| variant.bandwidth <= bitrate)
+                        .map_or(0, |index| index + 1),
+                    QualityLock::Auto => 0,
+                };
+                (labels, selected)
+            };
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            // Showing the current lock must not replace it, e.g. `--quality 2500k` with the
+            // variant it allows
+            quality_dropdown.block_signal(&quality_handler);
+            quality_dropdown.set_model(Some(&gtk::StringList::new(&labels)));
+            quality_dropdown.set_selected(selected as u32);
+            quality_dropdown.unblock_signal(&quality_handler);
+            quality_box.set_visible(labels.len() > 1);
+        }
+    ));
+
+    let decoder_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .visible(false)
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        decoder_label,
+        move |event| {
+            if !matches!(
+                event,
+                PlayerEvent::DecoderChanged | PlayerEvent::MediaChanged
+            ) {
+                return;
+            }
+            let media_player_ref = media_player.borrow();
+            let decoder = media_player_ref.video_decoder();
+            decoder_label.set_visible(decoder.is_some());
+            if let Some(decoder) = decoder {
+                decoder_label.set_label(&format!(
+                    "Decoder: {} ({})",
+                    decoder.name,
+                    if decoder.hardware {
+                        "hardware"
+                    } else {
+                        "software"
+                    }
+                ));
+            }
+        }
+    ));
+
+    let queue_label = Label::builder()
+        .halign(gtk::Align::Start)
+        .visible(false)
+        .build();
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        queue_label,
+        move |event| {
+            if !matches!(event, PlayerEvent::QueueChanged) {
+                return;
+            }
+            let position = media_player.borrow().queue_position();
+            queue_label.set_visible(position.is_some());
+            if let Some((index, length)) = position {
+                queue_label.set_label(&format!("Playlist: {} of {}", index + 1, length));
+            }
+        }
+    ));
+
+    // Only shown while the audio is shifted
+    let av_offset_label = Label::builder().halign(gtk::Align::Start).build();
+    let show_av_offset = move |label: &Label, av_offset: i64| {
+        label.set_visible(av_offset != 0);
+        label.set_label(&format!("A/V offset: {:+} ms", av_offset));
+    };
+    show_av_offset(&av_offset_label, media_player.borrow().av_offset());
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        av_offset_label,
+        move |event| {
+            if matches!(event, PlayerEvent::AvOffsetChanged) {
+                show_av_offset(&av_offset_label, media_player.borrow().av_offset());
+            }
+        }
+    ));
+
+    info_box.append(&normalization_label);
+    info_box.append(&decoder_label);
+    info_box.append(&av_offset_label);
+    info_box.append(&queue_label);
+    info_box.append(&variant_label);
+    info_box.append(&quality_box);
+
+    glib::timeout_add_seconds_local(
+        1,
+        clone!(
+            #[weak]
+            media_player,
+            #[weak]
+            normalization_label,
+            #[weak]
+            variant_label,
+            #[upgrade_or]
+            glib::ControlFlow::Break,
+            move || {
+                let media_player_ref = media_player.borrow();
+                let gain = media_player_ref
+                    .applied_gain()
+                    .map_or_else(|| "-".to_string(), |gain| format!("{:+.1} dB", gain));
+                normalization_label.set_label(&format!(
+                    "Normalization: {:?} (gain {})",
+                    media_player_ref.normalization(),
+                    gain
+                ));
+
+                let bandwidth = media_player_ref.bandwidth().map_or_else(
+                    || "-".to_string(),
+                    |bandwidth| format!("{:.1} Mbps", bandwidth as f64 / 1_000_000.0),
+                );
+                let variant = media_player_ref
+                    .current_variant()
+                    .map_or_else(|| "-".to_string(), Variant::label);
+                variant_label.set_visible(!media_player_ref.variants().is_empty());
+                variant_label.set_label(&format!("Variant: {} (bandwidth {})", variant, bandwidth));
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+
+    info_box
+}
+
 pub fn build_ui(app: &Application, media_player: MediaPlayerRef) {
     let window: ApplicationWindow = ApplicationWindow::builder()
         .application(app)
         .title("AugMediaPlayer")
         .build();
 
+    let header_bar = gtk::HeaderBar::new();
+    header_bar.pack_end(&build_menu_button(&media_player, &window));
+    header_bar.pack_end(&build_record_button(&media_player));
+    header_bar.pack_end(&build_video_adjustments(&media_player, &window));
+    window.set_titlebar(Some(&header_bar));
+
     let button_box = build_buttons(&media_player, &window);
     let volume_box = build_volume_controls(&media_player, &window);
+    let info_box = build_info_panel(&media_player);
 
     let control_box = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
@@ -335,16 +2817,104 @@ pub fn build_ui(app: &Application, media_player: MediaPlayerRef) {
         .build();
 
     refresh_ui(&window, &media_player, &duration_bar);
+    build_loop_actions(&media_player, &window, &duration_bar);
+    build_chapter_actions(&media_player, &window);
+    build_recording_actions(&media_player, &window);
+    build_video_transform_actions(&media_player, &window);
+    build_deinterlace_actions(&media_player, &window);
+    build_av_offset_actions(&media_player, &window);
+    build_history_actions(&media_player, &window);
+    build_playlist_actions(&media_player, &window);
+    build_library_actions(&media_player, &Library::open(), &window);
+    build_bookmark_actions(&media_player, &window);
+    let export_box = build_export_controls(&media_player, &window, &duration_bar);
+
+    // Chapter ticks need both the chapter list and the duration
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        media_player,
+        #[weak]
+        duration_bar,
+        move |event| {
+            if matches!(
+                event,
+                PlayerEvent::ChaptersChanged
+                    | PlayerEvent::BookmarksChanged
+                    | PlayerEvent::DurationChanged
+                    | PlayerEvent::MediaChanged
+            ) {
+                update_duration_marks(&duration_bar, &media_player.borrow());
+            }
+        }
+    ));
+
+    let chapter_label = build_chapter_label(&media_player);
+    let buffering_bar = build_buffering_indicator(&media_player);
+    let live_label = build_live_indicator(&media_player);
+    let recording_label = build_recording_indicator(&media_player);
+
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        duration_bar,
+        move |event| {
+            if let PlayerEvent::SeekingChanged { seekable, .. } = *event {
+                duration_bar.set_sensitive(seekable);
+            }
+        }
+    ));
 
     let video_widget = media_player.borrow().get_gtk_widget();
     video_widget.set_size_request(640, 360);
     
-    control_box.append(&video_widget);
+    // Cover art and messages are shown over the video
+    let video_overlay = gtk::Overlay::builder().child(&video_widget).build();
+    video_overlay.add_overlay(&build_cover_art(&media_player));
+    video_overlay.add_overlay(&build_osd(&media_player));
+
+    // The start page stays until a media is opened
+    let video_stack = gtk::Stack::new();
+    video_stack.add_named(&build_start_page(&media_player), Some("start"));
+    video_stack.add_named(&video_overlay, Some("video"));
+    if !media_player.borrow().uri().is_empty() {
+        video_stack.set_visible_child_name("video");
+    }
+    media_player.borrow().connect_event(clone!(
+        #[weak]
+        video_stack,
+        move |event| {
+            if matches!(event, PlayerEvent::MediaChanged) {
+                video_stack.set_visible_child_name("video");
+            }
+        }
+    ));
+    // The bookmarks sidebar slides in on the side of the video
+    video_stack.set_hexpand(true);
+    let video_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+    video_box.append(&video_stack);
+    video_box.append(&build_bookmarks_sidebar(&media_player, &window));
+    control_box.append(&video_box);
+    control_box.append(&buffering_bar);
+    control_box.append(&chapter_label);
+    control_box.append(&live_label);
+    control_box.append(&recording_label);
     control_box.append(&duration_bar);
     control_box.append(&button_box);
     control_box.append(&volume_box);
+    control_box.append(&export_box);
+    control_box.append(&info_box);
 
     window.set_child(Some(&control_box));
 
+    window.connect_close_request(clone!(
+        #[weak]
+        media_player,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_| {
+            media_player.borrow().remember_position();
+            glib::Propagation::Proceed
+        }
+    ));
+
     window.present();
 }
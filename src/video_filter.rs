@@ -0,0 +1,309 @@
+use {
+    crate::{
+        media_settings::MediaSettings,
+        video_transform::{Orientation, VideoTransform},
+    },
+    clap::ValueEnum,
+    gstreamer::{prelude::*, *},
+    std::sync::{Arc, Mutex},
+};
+
+/// Name of the application message posted when the orientation tag of the media is read
+pub const ORIENTATION_CHANGED: &str = "aug-orientation-changed";
+
+/// When the video is deinterlaced
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Deinterlace {
+    /// Deinterlace the media whose caps say it is interlaced
+    #[default]
+    Auto,
+    /// Always deinterlace, for interlaced media flagged as progressive
+    On,
+    /// Never deinterlace
+    Off,
+}
+
+impl Deinterlace {
+    /// Value of the `mode` property of `deinterlace`
+    fn mode(&self) -> &'static str {
+        match self {
+            Deinterlace::Auto => "auto",
+            Deinterlace::On => "interlaced",
+            Deinterlace::Off => "disabled",
+        }
+    }
+}
+
+/// Algorithms of `deinterlace`, named after its `method` property values
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DeinterlaceMethod {
+    /// Motion adaptive, motion search
+    Tomsmocomp,
+    /// Motion adaptive, advanced detection
+    #[default]
+    Greedyh,
+    /// Motion adaptive, simple detection
+    Greedyl,
+    /// Vertical blur
+    Vfir,
+    /// Linear interpolation
+    Linear,
+    /// Linear blend
+    Linearblend,
+    /// Scaler bob, halves the vertical resolution
+    Scalerbob,
+    /// Weave, keeps the combing of moving parts
+    Weave,
+    /// Weave, top field first
+    Weavetff,
+    /// Weave, bottom field first
+    Weavebff,
+}
+
+/// Adjustments of the picture, applied by `videobalance`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoBalance {
+    /// From -1.0 to 1.0
+    pub brightness: f64,
+    /// From 0.0 to 2.0
+    pub contrast: f64,
+    /// From 0.0 to 2.0
+    pub saturation: f64,
+    /// From -1.0 to 1.0
+    pub hue: f64,
+}
+
+impl Default for VideoBalance {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+        }
+    }
+}
+
+impl VideoBalance {
+    /// Names of the adjustments, as `videobalance` properties and setting keys
+    pub const PROPERTIES: [&'static str; 4] = ["brightness", "contrast", "saturation", "hue"];
+
+    fn values(&self) -> [f64; 4] {
+        [self.brightness, self.contrast, self.saturation, self.hue]
+    }
+
+    /// Adjustments saved for the media, the defaults when none were saved
+    pub fn load(settings: &MediaSettings, uri: &str) -> Self {
+        let default = Self::default();
+        let value = |key: &str, default: f64| settings.double(uri, key).unwrap_or(default);
+        Self {
+            brightness: value("brightness", default.brightness),
+            contrast: value("contrast", default.contrast),
+            saturation: value("saturation", default.saturation),
+            hue: value("hue", default.hue),
+        }
+    }
+
+    /// Remember the adjustments of the media. Default values are not stored.
+    pub fn store(&self, settings: &MediaSettings, uri: &str) {
+        let defaults = Self::default().values();
+        for ((key, value), default) in Self::PROPERTIES.iter().zip(self.values()).zip(defaults) {
+            if value == default {
+                settings.remove(uri, key);
+            } else {
+                settings.set_double(uri, key, value);
+            }
+        }
+    }
+}
+
+/// The `video-filter` bin installed in playbin.
+/// `deinterlace ! videoconvert ! videobalance ! videoflip ! aspectratiocrop ! videocrop ! videoscale ! capsfilter`
+pub struct VideoFilter {
+    /// The bin with ghost pads, ready to be set as playbin's `video-filter`
+    pub bin: Bin,
+    /// Deinterlacing of interlaced media, see [`Deinterlace`]
+    pub deinterlace: Element,
+    /// Brightness, contrast, saturation and hue. Passthrough at the default values.
+    pub balance: Element,
+    /// Rotations and flips
+    pub flip: Element,
+    /// Crop to an aspect ratio, passthrough at 0/1
+    pub crop: Element,
+    /// Crop to the zoomed area
+    pub zoom: Element,
+    /// Size of the picture when the aspect ratio is forced, `videoscale` stretches to it
+    pub aspect: Element,
+    /// Geometry of the picture, shared with the streaming thread as the zoom and the
+    /// forced aspect ratio depend on the size of the picture
+    transform: Arc<Mutex<VideoTransform>>,
+    /// Size of the picture reaching the zoom, once negotiated
+    size: Arc<Mutex<Option<(i32, i32)>>>,
+}
+
+impl VideoFilter {
+    pub fn build() -> Result<Self, glib::Error> {
+        let bin = parse::bin_from_description(
+            "deinterlace name=deinterlace ! videoconvert ! videobalance name=balance \
+             ! videoflip name=flip ! aspectratiocrop name=crop ! videocrop name=zoom \
+             ! videoscale ! capsfilter name=aspect",
+            true,
+        )?;
+        bin.set_property("name", "video-filter");
+        // The elements are created by the description above, so they are always present
+        let deinterlace = bin.by_name("deinterlace").unwrap();
+        let balance = bin.by_name("balance").unwrap();
+        let flip = bin.by_name("flip").unwrap();
+        let crop = bin.by_name("crop").unwrap();
+        let zoom = bin.by_name("zoom").unwrap();
+        let aspect = bin.by_name("aspect").unwrap();
+
+        let transform = Arc::new(Mutex::new(VideoTransform::default()));
+        let size = Arc::new(Mutex::new(None));
+        // Rotations and crops change the size of the picture, so the zoom and the aspect
+        // ratio are computed again for each new size
+        if let Some(sink_pad) = zoom.static_pad("sink") {
+            let (flip_weak, aspect_weak) = (flip.downgrade(), aspect.downgrade());
+            let (transform, size) = (transform.clone(), size.clone());
+            sink_pad.add_probe(PadProbeType::EVENT_DOWNSTREAM, move |pad, info| {
+                let Some(event) = info.event() else {
+                    return PadProbeReturn::Ok;
+                };
+                let replacement = match event.view() {
+                    EventView::Caps(caps) => {
+                        if let Some(structure) = caps.caps().structure(0)
+                            && let (Ok(width), Ok(height)) = (
+                                structure.get::<i32>("width"),
+                                structure.get::<i32>("height"),
+                            )
+                        {
+                            *size.lock().unwrap() = Some((width, height));
+                            if let (Some(zoom), Some(aspect)) =
+                                (pad.parent_element(), aspect_weak.upgrade())
+                            {
+                                apply_geometry(
+                                    &zoom,
+                                    &aspect,
+                                    &transform.lock().unwrap(),
+                                    (width, height),
+                                );
+                            }
+                        }
+                        None
+                    }
+                    // videoflip rotates the picture following the tag, combined with the
+                    // user rotations, so the sink must not rotate it again
+                    EventView::Tag(tag) => tag.tag().get::<tags::ImageOrientation>().map(|value| {
+                        if let (Some(orientation), Some(flip), Some(zoom)) = (
+                            Orientation::from_tag(value.get()),
+                            flip_weak.upgrade(),
+                            pad.parent_element(),
+                        ) {
+                            let mut transform = transform.lock().unwrap();
+                            transform.tag_orientation = orientation;
+                            apply_orientation(&flip, &transform);
+                            let structure = Structure::builder(ORIENTATION_CHANGED).build();
+                            zoom.post_message(
+                                message::Application::builder(structure).src(&zoom).build(),
+                            )
+                            .ok();
+                        }
+                        let mut tag_list = tag.tag_owned();
+                        tag_list
+                            .make_mut()
+                            .add::<tags::ImageOrientation>(&"rotate-0", TagMergeMode::Replace);
+                        event::Tag::new(tag_list)
+                    }),
+                    _ => None,
+                };
+                if let Some(event) = replacement {
+                    info.data = Some(PadProbeData::Event(event));
+                }
+                PadProbeReturn::Ok
+            });
+        }
+
+        Ok(Self {
+            bin,
+            deinterlace,
+            balance,
+            flip,
+            crop,
+            zoom,
+            aspect,
+            transform,
+            size,
+        })
+    }
+
+    pub fn set_deinterlace(&self, mode: Deinterlace, method: DeinterlaceMethod) {
+        self.deinterlace.set_property_from_str("mode", mode.mode());
+        // The variants are named after the property values
+        if let Some(method) = method.to_possible_value() {
+            self.deinterlace
+                .set_property_from_str("method", method.get_name());
+        }
+    }
+
+    pub fn set_balance(&self, balance: &VideoBalance) {
+        for (property, value) in VideoBalance::PROPERTIES.iter().zip(balance.values()) {
+            self.balance.set_property(*property, value);
+        }
+    }
+
+    pub fn transform(&self) -> VideoTransform {
+        *self.transform.lock().unwrap()
+    }
+
+    pub fn set_transform(&self, transform: &VideoTransform) {
+        *self.transform.lock().unwrap() = *transform;
+        apply_orientation(&self.flip, transform);
+        self.crop.set_property(
+            "aspect-ratio",
+            transform.crop.unwrap_or(Fraction::new(0, 1)),
+        );
+        let size = *self.size.lock().unwrap();
+        if let Some(size) = size {
+            apply_geometry(&self.zoom, &self.aspect, transform, size);
+        }
+    }
+}
+
+fn apply_orientation(flip: &Element, transform: &VideoTransform) {
+    flip.set_property_from_str(
+        "video-direction",
+        transform.effective_orientation().video_direction(),
+    );
+}
+
+/// Crop the zoomed area of a `width`×`height` picture, and stretch it to the forced
+/// aspect ratio if any
+fn apply_geometry(
+    zoom: &Element,
+    aspect: &Element,
+    transform: &VideoTransform,
+    (width, height): (i32, i32),
+) {
+    let (left, right, top, bottom) = transform.zoom_crop(width, height);
+    for (property, value) in [
+        ("left", left),
+        ("right", right),
+        ("top", top),
+        ("bottom", bottom),
+    ] {
+        zoom.set_property(property, value);
+    }
+
+    let mut caps = Caps::builder("video/x-raw");
+    if let Some(ratio) = transform.aspect_ratio {
+        // The height is kept, the width follows the ratio with square pixels
+        let height = height - top - bottom;
+        let width =
+            (i64::from(height) * i64::from(ratio.numer()) / i64::from(ratio.denom())) as i32;
+        caps = caps
+            .field("width", width.max(2) & !1)
+            .field("height", height)
+            .field("pixel-aspect-ratio", Fraction::new(1, 1));
+    }
+    aspect.set_property("caps", caps.build());
+}
@@ -0,0 +1,266 @@
+use gstreamer::Fraction;
+
+/// Largest zoom factor
+pub const MAX_ZOOM: f64 = 8.0;
+
+/// Aspect ratios offered for the crop and the aspect ratio override
+pub const ASPECT_PRESETS: [(&str, i32, i32); 4] = [
+    ("4:3", 4, 3),
+    ("16:9", 16, 9),
+    ("2.35:1", 47, 20),
+    ("1:1", 1, 1),
+];
+
+/// Aspect ratio of a preset label such as `16:9`
+pub fn aspect_preset(label: &str) -> Option<Fraction> {
+    ASPECT_PRESETS
+        .iter()
+        .find(|(name, _, _)| *name == label)
+        .map(|(_, numerator, denominator)| Fraction::new(*numerator, *denominator))
+}
+
+/// Orientation of the picture: mirrored horizontally if `flipped`, then rotated
+/// clockwise by `quarter_turns` × 90°
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Orientation {
+    pub quarter_turns: u8,
+    pub flipped: bool,
+}
+
+impl Orientation {
+    pub const IDENTITY: Self = Self {
+        quarter_turns: 0,
+        flipped: false,
+    };
+    pub const ROTATE_CLOCKWISE: Self = Self {
+        quarter_turns: 1,
+        flipped: false,
+    };
+    pub const ROTATE_COUNTERCLOCKWISE: Self = Self {
+        quarter_turns: 3,
+        flipped: false,
+    };
+    pub const FLIP_HORIZONTAL: Self = Self {
+        quarter_turns: 0,
+        flipped: true,
+    };
+    pub const FLIP_VERTICAL: Self = Self {
+        quarter_turns: 2,
+        flipped: true,
+    };
+
+    /// Orientation applying `self`, then `next`
+    pub fn then(self, next: Self) -> Self {
+        // A mirror reverses the direction of the rotations made before it
+        let turns = if next.flipped {
+            4 - self.quarter_turns % 4
+        } else {
+            self.quarter_turns
+        };
+        Self {
+            quarter_turns: (next.quarter_turns + turns) % 4,
+            flipped: self.flipped != next.flipped,
+        }
+    }
+
+    /// Orientation restoring a picture with this `image-orientation` tag, e.g. `rotate-90`
+    /// or `flip-rotate-270`
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let (flipped, degrees) = match tag.strip_prefix("flip-rotate-") {
+            Some(degrees) => (true, degrees),
+            None => (false, tag.strip_prefix("rotate-")?),
+        };
+        let quarter_turns = match degrees {
+            "0" => 0,
+            "90" => 1,
+            "180" => 2,
+            "270" => 3,
+            _ => return None,
+        };
+        // `flip-rotate-*` rotates first and mirrors afterwards
+        Some(if flipped {
+            Self::ROTATE_CLOCKWISE_BY[quarter_turns].then(Self::FLIP_HORIZONTAL)
+        } else {
+            Self::ROTATE_CLOCKWISE_BY[quarter_turns]
+        })
+    }
+
+    const ROTATE_CLOCKWISE_BY: [Self; 4] = [
+        Self::IDENTITY,
+        Self::ROTATE_CLOCKWISE,
+        Self {
+            quarter_turns: 2,
+            flipped: false,
+        },
+        Self::ROTATE_COUNTERCLOCKWISE,
+    ];
+
+    /// Value of `videoflip`'s `video-direction` property
+    pub fn video_direction(&self) -> &'static str {
+        match (self.quarter_turns % 4, self.flipped) {
+            (0, false) => "identity",
+            (1, false) => "90r",
+            (2, false) => "180",
+            (3, false) => "90l",
+            (0, true) => "horiz",
+            (1, true) => "ur-ll",
+            (2, true) => "vert",
+            _ => "ul-lr",
+        }
+    }
+}
+
+/// Geometry changes applied to the picture by the video filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoTransform {
+    /// Orientation read from the `image-orientation` tag of the media
+    pub tag_orientation: Orientation,
+    /// Rotations and flips chosen by the user, applied after the tag orientation
+    pub orientation: Orientation,
+    /// Crop the picture to this aspect ratio, e.g. to remove letterboxing
+    pub crop: Option<Fraction>,
+    /// Zoom factor, from 1.0 to [`MAX_ZOOM`]
+    pub zoom: f64,
+    /// Center of the zoomed area, from -1.0 (left, top) to 1.0 (right, bottom)
+    pub pan: (f64, f64),
+    /// Stretch the picture to this display aspect ratio
+    pub aspect_ratio: Option<Fraction>,
+}
+
+impl Default for VideoTransform {
+    fn default() -> Self {
+        Self {
+            tag_orientation: Orientation::IDENTITY,
+            orientation: Orientation::IDENTITY,
+            crop: None,
+            zoom: 1.0,
+            pan: (0.0, 0.0),
+            aspect_ratio: None,
+        }
+    }
+}
+
+impl VideoTransform {
+    /// Orientation given to `videoflip`
+    pub fn effective_orientation(&self) -> Orientation {
+        self.tag_orientation.then(self.orientation)
+    }
+
+    pub fn rotate(&mut self, clockwise: bool) {
+        let rotation = if clockwise {
+            Orientation::ROTATE_CLOCKWISE
+        } else {
+            Orientation::ROTATE_COUNTERCLOCKWISE
+        };
+        self.orientation = self.orientation.then(rotation);
+    }
+
+    pub fn flip(&mut self, horizontal: bool) {
+        let flip = if horizontal {
+            Orientation::FLIP_HORIZONTAL
+        } else {
+            Orientation::FLIP_VERTICAL
+        };
+        self.orientation = self.orientation.then(flip);
+    }
+
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+        if self.zoom == 1.0 {
+            self.pan = (0.0, 0.0);
+        }
+    }
+
+    /// Move the zoomed area, by a fraction of the visible area
+    pub fn pan_by(&mut self, dx: f64, dy: f64) {
+        if self.zoom <= 1.0 {
+            return;
+        }
+        // The pan range covers the hidden part of the picture, which grows with the zoom
+        let step = 2.0 / (self.zoom - 1.0);
+        self.pan = (
+            (self.pan.0 + dx * step).clamp(-1.0, 1.0),
+            (self.pan.1 + dy * step).clamp(-1.0, 1.0),
+        );
+    }
+
+    /// Forget the user changes, keeping the orientation of the media
+    pub fn reset(&mut self) {
+        *self = Self {
+            tag_orientation: self.tag_orientation,
+            ..Self::default()
+        };
+    }
+
+    /// Pixels to crop on the left, right, top and bottom of a `width`×`height` picture
+    /// to show the zoomed area
+    pub fn zoom_crop(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        let split = |size: i32, pan: f64| {
+            let hidden = size as f64 * (1.0 - 1.0 / self.zoom);
+            let before = (hidden * (1.0 + pan) / 2.0).round() as i32;
+            (before, hidden.round() as i32 - before)
+        };
+        let (left, right) = split(width, self.pan.0);
+        let (top, bottom) = split(height, self.pan.1);
+        (left, right, top, bottom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The 8 orientations with their `image-orientation` tag and `video-direction`
+    const ORIENTATIONS: [(&str, &str); 8] = [
+        ("rotate-0", "identity"),
+        ("rotate-90", "90r"),
+        ("rotate-180", "180"),
+        ("rotate-270", "90l"),
+        ("flip-rotate-0", "horiz"),
+        ("flip-rotate-90", "ul-lr"),
+        ("flip-rotate-180", "vert"),
+        ("flip-rotate-270", "ur-ll"),
+    ];
+
+    /// Where an orientation moves the point `(x, y)`, with y pointing down
+    fn apply(orientation: Orientation, (x, y): (i32, i32)) -> (i32, i32) {
+        let mut point = if orientation.flipped { (-x, y) } else { (x, y) };
+        for _ in 0..orientation.quarter_turns {
+            point = (-point.1, point.0);
+        }
+        point
+    }
+
+    #[test]
+    fn tags_and_video_directions() {
+        for (tag, direction) in ORIENTATIONS {
+            let orientation = Orientation::from_tag(tag).unwrap();
+            assert_eq!(orientation.video_direction(), direction, "{tag}");
+        }
+        assert_eq!(Orientation::from_tag("rotate-45"), None);
+        assert_eq!(Orientation::from_tag("flip-90"), None);
+    }
+
+    #[test]
+    fn then_composes_the_orientations() {
+        let orientations = ORIENTATIONS.map(|(tag, _)| Orientation::from_tag(tag).unwrap());
+        for first in orientations {
+            assert_eq!(first.then(Orientation::IDENTITY), first);
+            assert_eq!(Orientation::IDENTITY.then(first), first);
+            for next in orientations {
+                let both = first.then(next);
+                for point in [(1, 0), (0, 1), (2, 3)] {
+                    assert_eq!(
+                        apply(both, point),
+                        apply(next, apply(first, point)),
+                        "{first:?} then {next:?}"
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            Orientation::FLIP_HORIZONTAL.then(Orientation::FLIP_VERTICAL),
+            Orientation::from_tag("rotate-180").unwrap()
+        );
+    }
+}
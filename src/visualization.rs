@@ -0,0 +1,77 @@
+use {
+    crate::media_player::set_play_flag,
+    clap::ValueEnum,
+    gstreamer::{prelude::*, *},
+    gtk::gdk,
+};
+
+/// What is shown in place of the picture of audio-only media
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Visualization {
+    /// The cover art embedded in the media
+    Cover,
+    /// Colorful shapes following the music
+    #[default]
+    Goom,
+    /// Frequency spectrum
+    Spectrascope,
+    /// Waveform
+    Wavescope,
+    /// Oscilloscope
+    Monoscope,
+    /// Nothing
+    Off,
+}
+
+impl Visualization {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Visualization::Cover => "Cover Art",
+            Visualization::Goom => "Goom",
+            Visualization::Spectrascope => "Spectrum",
+            Visualization::Wavescope => "Waveform",
+            Visualization::Monoscope => "Oscilloscope",
+            Visualization::Off => "Off",
+        }
+    }
+
+    /// Element factory of the visualizer, `None` when playbin shows no visualization
+    pub fn plugin(&self) -> Option<&'static str> {
+        match self {
+            Visualization::Goom => Some("goom"),
+            Visualization::Spectrascope => Some("spectrascope"),
+            Visualization::Wavescope => Some("wavescope"),
+            Visualization::Monoscope => Some("monoscope"),
+            Visualization::Cover | Visualization::Off => None,
+        }
+    }
+}
+
+/// Set the `vis-plugin` of playbin and its `vis` flag. playbin only plugs the visualizer
+/// when the media has no video stream, and can switch it during playback.
+pub fn apply_visualization(
+    playbin: &Element,
+    visualization: Visualization,
+) -> Result<(), glib::BoolError> {
+    let plugin = visualization
+        .plugin()
+        .map(|name| ElementFactory::make(name).build())
+        .transpose()?;
+
+    set_play_flag(playbin, "vis", plugin.is_some());
+    if let Some(plugin) = plugin {
+        playbin.set_property("vis-plugin", &plugin);
+    }
+    Ok(())
+}
+
+/// Decode the cover art carried by the `image` tag, or else the `preview-image` tag
+pub fn cover_art(tag_list: &TagListRef) -> Option<gdk::Texture> {
+    let sample = tag_list
+        .get::<tags::Image>()
+        .or_else(|| tag_list.get::<tags::PreviewImage>())?
+        .get();
+    let buffer = sample.buffer()?;
+    let map = buffer.map_readable().ok()?;
+    gdk::Texture::from_bytes(&glib::Bytes::from(map.as_slice())).ok()
+}